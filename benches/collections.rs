@@ -0,0 +1,144 @@
+// The crate is a binary, so its source is compiled into the benchmark as a module.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/main.rs"]
+mod chunk_list;
+
+use std::{
+    collections::{LinkedList, VecDeque},
+    hint::black_box,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use chunk_list::ChunkList;
+
+const LEN: usize = 10_000;
+
+/// An element much larger than a word, to show the cost of moving elements.
+type Large = [u64; 8];
+
+/// Pseudo random indices, the same for every collection.
+fn indices(len: usize) -> Vec<usize> {
+    let mut state = 0x2545_F491_4F6C_DD1D_u64;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % len
+        })
+        .collect()
+}
+
+/// Registers the benchmarks shared by all collections, `$name` labels them in the report.
+macro_rules! bench_deque {
+    ($c:expr, $element:ty, $name:expr, $collection:ty) => {{
+        let element_name = stringify!($element);
+        let id = |group: &str| format!("{}/{}/{}", group, element_name, $name);
+        let full = || (0..LEN).map(|_| <$element>::default()).collect::<$collection>();
+
+        $c.bench_function(&id("push_back"), |b| b.iter(|| {
+            let mut collection = <$collection>::new();
+            for _ in 0..LEN {
+                collection.push_back(black_box(<$element>::default()));
+            }
+            collection
+        }));
+        $c.bench_function(&id("push_front"), |b| b.iter(|| {
+            let mut collection = <$collection>::new();
+            for _ in 0..LEN {
+                collection.push_front(black_box(<$element>::default()));
+            }
+            collection
+        }));
+        $c.bench_function(&id("pop_back"), |b| b.iter_batched(full, |mut collection| {
+            while let Some(value) = collection.pop_back() {
+                black_box(value);
+            }
+        }, BatchSize::SmallInput));
+        $c.bench_function(&id("pop_front"), |b| b.iter_batched(full, |mut collection| {
+            while let Some(value) = collection.pop_front() {
+                black_box(value);
+            }
+        }, BatchSize::SmallInput));
+        $c.bench_function(&id("iter"), |b| {
+            let collection = full();
+            b.iter(|| collection.iter().map(black_box).count())
+        });
+    }};
+}
+
+/// Benchmarks for collections with positional access, LinkedList has none.
+macro_rules! bench_indexed {
+    ($c:expr, $element:ty, $name:expr, $collection:ty, $insert:ident, $remove:ident) => {{
+        let element_name = stringify!($element);
+        let id = |group: &str| format!("{}/{}/{}", group, element_name, $name);
+        let full = || (0..LEN).map(|_| <$element>::default()).collect::<$collection>();
+        let positions = indices(LEN);
+
+        $c.bench_function(&id("random_access"), |b| {
+            let collection = full();
+            b.iter(|| positions.iter().map(|&i| black_box(&collection[i])).count())
+        });
+        $c.bench_function(&id("insert_middle"), |b| b.iter_batched(full, |mut collection| {
+            for inserted in 0..1000 {
+                let middle = (LEN + inserted) / 2;
+                collection.$insert(middle, <$element>::default());
+            }
+            collection
+        }, BatchSize::SmallInput));
+        $c.bench_function(&id("remove_middle"), |b| b.iter_batched(full, |mut collection| {
+            for removed in 0..1000 {
+                let middle = (LEN - removed - 1) / 2;
+                black_box(collection.$remove(middle));
+            }
+            collection
+        }, BatchSize::SmallInput));
+    }};
+}
+
+/// `Vec` has no front operations, so it only takes part in the shared benchmarks that make sense.
+fn vec(c: &mut Criterion) {
+    macro_rules! bench_vec {
+        ($element:ty) => {{
+            let element_name = stringify!($element);
+            let id = |group: &str| format!("{}/{}/Vec", group, element_name);
+            c.bench_function(&id("push_back"), |b| b.iter(|| {
+                let mut collection = Vec::new();
+                for _ in 0..LEN {
+                    collection.push(black_box(<$element>::default()));
+                }
+                collection
+            }));
+            c.bench_function(&id("iter"), |b| {
+                let collection = vec![<$element>::default(); LEN];
+                b.iter(|| collection.iter().map(black_box).count())
+            });
+            bench_indexed!(c, $element, "Vec", Vec<$element>, insert, remove);
+        }};
+    }
+    bench_vec!(u64);
+    bench_vec!(Large);
+}
+
+fn std_deques(c: &mut Criterion) {
+    bench_deque!(c, u64, "VecDeque", VecDeque<u64>);
+    bench_deque!(c, Large, "VecDeque", VecDeque<Large>);
+    bench_indexed!(c, u64, "VecDeque", VecDeque<u64>, insert, remove);
+    bench_indexed!(c, Large, "VecDeque", VecDeque<Large>, insert, remove);
+    bench_deque!(c, u64, "LinkedList", LinkedList<u64>);
+    bench_deque!(c, Large, "LinkedList", LinkedList<Large>);
+}
+
+fn chunk_lists(c: &mut Criterion) {
+    macro_rules! bench_chunk_list {
+        ($($n:literal),*) => {$(
+            bench_deque!(c, u64, concat!("ChunkList<", $n, ">"), ChunkList<u64, $n>);
+            bench_deque!(c, Large, concat!("ChunkList<", $n, ">"), ChunkList<Large, $n>);
+            bench_indexed!(c, u64, concat!("ChunkList<", $n, ">"), ChunkList<u64, $n>, insert, remove);
+            bench_indexed!(c, Large, concat!("ChunkList<", $n, ">"), ChunkList<Large, $n>, insert, remove);
+        )*};
+    }
+    bench_chunk_list!(16, 64, 256);
+}
+
+criterion_group!(benches, vec, std_deques, chunk_lists);
+criterion_main!(benches);
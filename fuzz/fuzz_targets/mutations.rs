@@ -0,0 +1,96 @@
+#![no_main]
+
+// The crate is a binary, so its source is compiled into the fuzz target as a module.
+#[allow(dead_code)]
+#[path = "../../src/main.rs"]
+mod chunk_list;
+
+use std::collections::VecDeque;
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use chunk_list::ChunkList;
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    PushBack(u8),
+    PushFront(u8),
+    PopBack,
+    PopFront,
+    Insert(usize, u8),
+    Remove(usize),
+    Get(usize),
+    Set(usize, u8),
+    SplitOff(usize),
+    Append(Vec<u8>),
+    Truncate(usize),
+    Retain(u8),
+}
+
+const N: usize = 4;
+
+fuzz_target!(|operations: Vec<Operation>| {
+    let mut list = ChunkList::<u8, N>::new();
+    let mut model = VecDeque::new();
+    for operation in operations {
+        // Positions are kept one past the end at most, so out of bounds accesses are exercised too.
+        let position = |i: usize, len: usize| i % (len + 2);
+        match operation {
+            Operation::PushBack(value) => {
+                list.push_back(value);
+                model.push_back(value);
+            }
+            Operation::PushFront(value) => {
+                list.push_front(value);
+                model.push_front(value);
+            }
+            Operation::PopBack => assert_eq!(list.pop_back(), model.pop_back()),
+            Operation::PopFront => assert_eq!(list.pop_front(), model.pop_front()),
+            Operation::Insert(i, value) => {
+                let i = position(i, model.len()).min(model.len());
+                list.insert(i, value);
+                model.insert(i, value);
+            }
+            Operation::Remove(i) => {
+                let i = position(i, model.len());
+                assert_eq!(list.remove(i), model.remove(i));
+            }
+            Operation::Get(i) => {
+                let i = position(i, model.len());
+                assert_eq!(list.get(i), model.get(i));
+            }
+            Operation::Set(i, value) => {
+                let i = position(i, model.len());
+                if let (Some(element), Some(expected)) = (list.get_mut(i), model.get_mut(i)) {
+                    *element = value;
+                    *expected = value;
+                }
+            }
+            Operation::SplitOff(i) => {
+                let i = position(i, model.len()).min(model.len());
+                let tail = list.split_off(i);
+                let expected = model.split_off(i);
+                assert!(tail.iter().eq(&expected));
+            }
+            Operation::Append(values) => {
+                let mut other: ChunkList<u8, N> = values.iter().copied().collect();
+                list.append(&mut other);
+                model.extend(values);
+            }
+            Operation::Truncate(len) => {
+                let len = position(len, model.len());
+                list.truncate(len);
+                model.truncate(len);
+            }
+            Operation::Retain(modulus) => {
+                let modulus = modulus.max(1);
+                list.retain(|value| value % modulus != 0);
+                model.retain(|value| value % modulus != 0);
+            }
+        }
+        assert_eq!(list.elements_count(), model.len());
+        assert!(list.iter().eq(&model));
+        assert!(list.iter().rev().eq(model.iter().rev()));
+        #[cfg(feature = "check-invariants")]
+        list.check_invariants().unwrap();
+    }
+});
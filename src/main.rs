@@ -1,20 +1,160 @@
-use std::{mem, collections::{LinkedList, VecDeque}};
+use std::{
+    fmt, fs, io, mem, slice, thread,
+    cmp::Ordering,
+    collections::{vec_deque, HashMap, LinkedList, TryReserveError, VecDeque},
+    hash::{Hash, Hasher},
+    io::{Read, Seek, Write},
+    iter::{FusedIterator, Peekable},
+    marker::PhantomData,
+    ops::{Bound, Deref, DerefMut, Index, IndexMut, RangeBounds},
+    path::PathBuf,
+    sync::Arc,
+};
 
-pub struct Chunk<T, const N: usize> {
-    elements: Vec<T>,
+/// Chunk size N of a list, known at compile time for `Const<N>` and at runtime for `usize`.
+/// Lists and chunks keep it as a field, which takes no space for `Const<N>`.
+/// Sealed, the two impls below are the only ones.
+pub trait Capacity: Copy + Send + Sync + sealed::Sealed {
+    fn get(self) -> usize;
 }
 
-impl<T, const N: usize> Default for Chunk<T, N> {
+mod sealed {
+    pub trait Sealed {}
+
+    impl<const N: usize> Sealed for super::Const<N> {}
+
+    impl Sealed for usize {}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Const<const N: usize>;
+
+impl<const N: usize> Capacity for Const<N> {
+    fn get(self) -> usize {
+        N
+    }
+}
+
+impl Capacity for usize {
+    fn get(self) -> usize {
+        self
+    }
+}
+
+/// Elements are stored in a ring buffer so that both ends of a chunk are O(1).
+/// The buffer is kept contiguous to allow slice views: when a push wraps around,
+/// the elements are moved to the middle of a buffer with room at both ends, so
+/// pushes stay O(1) amortised. Such a buffer can hold up to 1.5N + 2 elements.
+pub struct Chunk<T, C> {
+    elements: VecDeque<T>,
+    chunk_size: C,
+}
+
+impl<T, const N: usize> Default for Chunk<T, Const<N>> {
     fn default() -> Self {
         Chunk::new()
     }
 }
 
-impl<T, const N: usize> Chunk<T, N> {
+impl<T: Clone, C: Capacity> Clone for Chunk<T, C> {
+    fn clone(&self) -> Self {
+        let mut chunk = Chunk::with_capacity(self.chunk_size, self.chunk_size.get());
+        chunk.extend(self.elements.iter().cloned());
+        chunk
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        if self.chunk_size.get() != source.chunk_size.get() {
+            *self = source.clone();
+            return
+        }
+        self.reserve_exact(source.len().saturating_sub(self.len()));
+        self.elements.clone_from(&source.elements);
+        self.elements.make_contiguous();
+    }
+}
+
+impl<T: fmt::Debug, C: Capacity> fmt::Debug for Chunk<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.elements).finish()
+    }
+}
+
+impl<T, const N: usize> Chunk<T, Const<N>> {
     pub fn new() -> Self {
+        Self::with_capacity(Const, N)
+    }
+}
+
+impl<T, C: Capacity> Chunk<T, C> {
+    fn with_capacity(chunk_size: C, capacity: usize) -> Self {
         Self {
-            elements: Vec::<T>::with_capacity(N),
+            elements: VecDeque::<T>::with_capacity(capacity),
+            chunk_size,
+        }
+    }
+
+    fn try_with_capacity(chunk_size: C, capacity: usize) -> Result<Self, TryReserveError> {
+        let mut elements = VecDeque::new();
+        elements.try_reserve_exact(capacity)?;
+        Ok(Self { elements, chunk_size })
+    }
+
+    /// Doubles the buffer when it runs out of room but never grows it past N.
+    fn grow(&mut self, additional: usize) {
+        if let Some(reserved) = self.growth(additional) {
+            self.reserve_exact(reserved);
+        }
+    }
+
+    fn try_grow(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        match self.growth(additional) {
+            Some(reserved) => self.try_reserve_exact(reserved),
+            None => Ok(()),
+        }
+    }
+
+    /// Number of elements `grow` reserves room for, None if the buffer has enough room.
+    fn growth(&self, additional: usize) -> Option<usize> {
+        let needed = self.len() + additional;
+        let capacity = self.elements.capacity();
+        (needed > capacity).then(|| (capacity * 2).min(self.chunk_size.get()).max(needed) - self.len())
+    }
+
+    /// With the `zeroize` feature the buffer is replaced rather than reallocated
+    /// so that the old one is wiped, see `release_vec`.
+    fn reserve_exact(&mut self, additional: usize) {
+        #[cfg(feature = "zeroize")]
+        if self.elements.capacity() < self.len() + additional {
+            self.replace_buffer(VecDeque::with_capacity(self.len() + additional));
+        }
+        self.elements.reserve_exact(additional);
+    }
+
+    fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        #[cfg(feature = "zeroize")]
+        if self.elements.capacity() < self.len() + additional {
+            let mut elements = VecDeque::new();
+            elements.try_reserve_exact(self.len() + additional)?;
+            self.replace_buffer(elements);
+        }
+        self.elements.try_reserve_exact(additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        #[cfg(feature = "zeroize")]
+        if self.elements.capacity() > self.len() {
+            self.replace_buffer(VecDeque::with_capacity(self.len()));
         }
+        self.elements.shrink_to_fit();
+        self.elements.make_contiguous();
+    }
+
+    /// Moves the elements to the front of the empty `elements` buffer and releases the old one.
+    fn replace_buffer(&mut self, elements: VecDeque<T>) {
+        let mut old = mem::replace(&mut self.elements, elements);
+        self.elements.extend(old.drain(..));
+        release_vec(Vec::from(old));
     }
 
     pub fn len(&self) -> usize {
@@ -26,7 +166,7 @@ impl<T, const N: usize> Chunk<T, N> {
     }
 
     pub fn is_full(&self) -> bool {
-        self.len() == N
+        self.len() == self.chunk_size.get()
     }
 
     /// Return false in case of chunk overflow.
@@ -34,302 +174,6705 @@ impl<T, const N: usize> Chunk<T, N> {
         if self.is_full(){
             return false
         }
-        self.elements.push(value);
+        self.grow(1);
+        self.elements.push_back(value);
+        if !self.elements.as_slices().1.is_empty() {
+            self.recenter();
+        }
         true
     }
 
+    /// Same as `push_back` for a chunk which isn't full, but returns an error instead of
+    /// aborting when an allocation fails, the value is dropped then.
+    fn try_push_back(&mut self, value: T) -> Result<(), TryReserveError> {
+        assert!(!self.is_full());
+        self.try_grow(1)?;
+        self.elements.push_back(value);
+        if !self.elements.as_slices().1.is_empty() {
+            let mut elements = VecDeque::new();
+            if let Err(error) = elements.try_reserve_exact(self.recentered_capacity()) {
+                // Popping the value restores the contiguous buffer from before the push.
+                self.elements.pop_back();
+                return Err(error)
+            }
+            self.recenter_into(elements);
+        }
+        Ok(())
+    }
+
+    /// Return false in case of chunk overflow.
     pub fn push_front(&mut self, value: T) -> bool {
         if self.is_full() {
             return false
         }
-        self.elements.push(value);
-        self.elements.rotate_right(1);
+        self.grow(1);
+        self.elements.push_front(value);
+        if !self.elements.as_slices().1.is_empty() {
+            self.recenter();
+        }
         true
     }
 
+    /// Moves the elements of a wrapped buffer to the middle of a buffer with room for at least
+    /// a sixth of its capacity at each end. That is O(capacity) and the next wrap takes at least
+    /// as many pushes as there is room, which makes pushes O(1) amortised.
+    fn recenter(&mut self) {
+        self.recenter_into(VecDeque::with_capacity(self.recentered_capacity()));
+    }
+
+    fn recentered_capacity(&self) -> usize {
+        let len = self.len();
+        self.elements.capacity().max(len + len / 2 + 2)
+    }
+
+    /// `elements` should be empty with room for `recentered_capacity` elements.
+    fn recenter_into(&mut self, elements: VecDeque<T>) {
+        let len = self.len();
+        let offset = (elements.capacity() - len) / 2;
+        // A fresh buffer is filled from its start, rotating it by `offset` afterwards
+        // moves the ring head to `offset` while restoring the order.
+        self.elements.rotate_right(offset % len);
+        self.replace_buffer(elements);
+        for _ in 0..offset {
+            let front = self.elements.pop_front().unwrap();
+            self.elements.push_back(front);
+        }
+    }
+
     pub fn pop_back(&mut self) -> Option<T> {
-        self.elements.pop()
+        self.elements.pop_back()
     }
 
     pub fn pop_front(&mut self) -> Option<T> {
-        self.remove(0)
+        self.elements.pop_front()
     }
 
     pub fn get(&self, i: usize) -> Option<&T> {
-        if i >= N {
+        if i >= self.chunk_size.get() {
             panic!()
         }
         self.elements.get(i)
     }
 
-    pub fn remove(&mut self, i: usize) -> Option<T> {
-        if i >= N {
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        if i >= self.chunk_size.get() {
             panic!()
         }
-        if i >= self.elements.len() {
-            return None
-        }
-        Some(self.elements.remove(i))
+        self.elements.get_mut(i)
     }
 
-
-
-}
-
-pub struct ChunkList<T, const N: usize> {
-    chunks: VecDeque<Chunk<T, N>>,
-    elements_count: usize,
-}
-
-impl<T, const N: usize> ChunkList<T, N> {
-    pub fn new() -> Self {
-        if N < 1 {
+    pub fn remove(&mut self, i: usize) -> Option<T> {
+        if i >= self.chunk_size.get() {
             panic!()
         }
-        ChunkList {
-            chunks: VecDeque::new(),
-            elements_count: 0,
-        }
+        self.elements.remove(i)
     }
 
-    /// Constructs the container with count copies of elements with value.
-    pub fn new_filled(count: usize, value: &T) -> Self
-    where T: Clone {
-        let mut chunk_list = Self::new();
-        for i in 0..count {
-            chunk_list.push_back(value.clone())
+    /// Return false in case of chunk overflow.
+    pub fn insert(&mut self, i: usize, value: T) -> bool {
+        if self.is_full() {
+            return false
         }
-        chunk_list
+        self.grow(1);
+        self.elements.insert(i, value);
+        self.elements.make_contiguous();
+        true
     }
 
-    pub fn add_new_chunk_front(&mut self) -> &mut Chunk<T, N> {
-        self.chunks.push_front(Chunk::new());
-        self.chunks.front_mut().unwrap()
+    pub fn try_get(&self, i: usize) -> Result<&T, ChunkListError> {
+        let len = self.len();
+        self.elements.get(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
     }
 
-    pub fn add_new_chunk_back(&mut self) -> &mut Chunk<T, N> {
-        self.chunks.push_back(Chunk::new());
-        self.chunks.back_mut().unwrap()
+    pub fn try_get_mut(&mut self, i: usize) -> Result<&mut T, ChunkListError> {
+        let len = self.len();
+        self.elements.get_mut(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
     }
 
-    pub fn remove_chunk(&mut self, i: usize) -> Option<Chunk<T, N>> {
-        self.chunks.remove(i)
+    pub fn try_remove(&mut self, i: usize) -> Result<T, ChunkListError> {
+        let len = self.len();
+        self.elements.remove(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
     }
 
-    pub fn push_back(&mut self, value: T) {
-        let chunk = match self.chunks.back_mut() {
-            Some(back) => back,
-            None => self.add_new_chunk_back(),
-        };
-        if chunk.is_full() {
-            self.add_new_chunk_back().push_back(value);
+    /// The value is dropped if it can't be inserted.
+    pub fn try_insert(&mut self, i: usize, value: T) -> Result<(), ChunkListError> {
+        if i > self.len() {
+            return Err(ChunkListError::IndexOutOfBounds { index: i, len: self.len() })
         }
-        else {
-            chunk.push_back(value);
+        match self.insert(i, value) {
+            true => Ok(()),
+            false => Err(ChunkListError::ChunkFull),
         }
-        self.elements_count += 1
     }
 
-    pub fn push_front(&mut self, value: T) {
-        let not_full_chunk = match self.chunks.front_mut() {
-            None => self.add_new_chunk_front(),
-            Some(chunk) => {
-                if chunk.is_full() {
-                    self.add_new_chunk_front()
-                } else {
-                    chunk
-                }
-            },
-        };
-        not_full_chunk.push_front(value);
-        self.elements_count += 1;
+    pub fn as_slice(&self) -> &[T] {
+        let (front, back) = self.elements.as_slices();
+        debug_assert!(back.is_empty());
+        front
     }
 
-    pub fn pop_back(&mut self) -> Option<T> {
-        let chunk = self.chunks.back_mut()?;
-        let value = chunk.pop_back().unwrap();
-        if chunk.is_empty() {
-            self.chunks.pop_back();
-        }
-        self.elements_count -= 1;
-        Some(value)
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        let (front, back) = self.elements.as_mut_slices();
+        debug_assert!(back.is_empty());
+        front
     }
 
-    pub fn pop_front(&mut self) -> Option<T> {
-        self.remove(0)
+    /// Appends elements without checking for overflow.
+    fn extend(&mut self, iter: impl IntoIterator<Item = T>) {
+        let iter = iter.into_iter();
+        let (lower, upper) = iter.size_hint();
+        self.grow(lower);
+        if upper == Some(lower) {
+            self.elements.extend(iter);
+        }
+        else {
+            for value in iter {
+                self.grow(1);
+                self.elements.push_back(value);
+            }
+        }
+        self.elements.make_contiguous();
     }
 
-    pub fn chunks_count(&self) -> usize {
-        self.chunks.len()
+    /// Copies the slice to the back without checking for overflow. `VecDeque` extends
+    /// from a slice of `Copy` elements with one copy per contiguous part of its buffer.
+    fn extend_copied(&mut self, slice: &[T])
+    where T: Copy {
+        self.grow(slice.len());
+        self.elements.extend(slice);
+        self.elements.make_contiguous();
     }
 
-    pub fn elements_count(&self) -> usize {
-        self.elements_count
+    /// Moves all elements of `other` to the back without checking for overflow.
+    fn append(&mut self, other: &mut Self) {
+        self.grow(other.len());
+        self.elements.append(&mut other.elements);
+        self.elements.make_contiguous();
     }
 
-    /// Deletes all elements in the list.
-    pub fn clear(&mut self)
-    where T: PartialEq {
-        self.chunks.clear();
+    fn into_vec(mut self) -> Vec<T> {
+        Vec::from(mem::take(&mut self.elements))
     }
+}
 
-    pub fn get(&self, i: usize) -> Option<&T> {
-        self.iter().nth(i)
-    }
+/// Drops a buffer which held elements of a list. With the `zeroize` feature the whole buffer
+/// is wiped after dropping the elements, `Chunk` buffers are released the same way on drop.
+fn release_vec<T>(vec: Vec<T>) {
+    #[cfg(feature = "zeroize")]
+    zeroize_impls::wipe(vec);
+    #[cfg(not(feature = "zeroize"))]
+    drop(vec);
+}
 
-    pub fn remove(&mut self, i: usize) -> Option<T> {
-        let mut chunk_i = 0;
-        let mut count = 0;
-        while let Some(chunk) = self.chunks.get_mut(chunk_i) {
-            if count + chunk.len() >= i {
-                break;
+/// Returned by the `try_*` methods in the cases where their counterparts panic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChunkListError {
+    IndexOutOfBounds { index: usize, len: usize },
+    /// The chunk already holds N elements.
+    ChunkFull,
+    ZeroChunkSize,
+    /// The bytes are not UTF-8, `valid_up_to` is where the offending sequence starts.
+    InvalidUtf8 { valid_up_to: usize },
+    /// A chunk passed to `from_raw_parts` holds more than N elements.
+    ChunkOverflow { chunk_i: usize, len: usize },
+    /// The elements count passed to `from_raw_parts` differs from the chunks.
+    ElementsCountMismatch { expected: usize, counted: usize },
+}
+
+impl fmt::Display for ChunkListError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChunkListError::IndexOutOfBounds { index, len } => {
+                write!(f, "index out of bounds: the len is {} but the index is {}", len, index)
+            }
+            ChunkListError::ChunkFull => write!(f, "chunk is full"),
+            ChunkListError::ZeroChunkSize => write!(f, "chunk size should be > 0"),
+            ChunkListError::InvalidUtf8 { valid_up_to } => {
+                write!(f, "invalid utf-8 sequence starting at byte {}", valid_up_to)
+            }
+            ChunkListError::ChunkOverflow { chunk_i, len } => {
+                write!(f, "chunk {} holds {} elements, more than the chunk size", chunk_i, len)
+            }
+            ChunkListError::ElementsCountMismatch { expected, counted } => {
+                write!(f, "elements count is {} but the chunks hold {} elements", expected, counted)
             }
-            count += chunk.len();
-            chunk_i += 1;
-        }
-        let chunk = self.chunks.get_mut(chunk_i)?;
-        let element_i = count - i;
-        let value = chunk.remove(element_i);
-        if chunk.is_empty() {
-            self.remove_chunk(i);
         }
-        self.elements_count -= 1;
-        value
     }
 }
 
-// --------------------
-// INTO ITER
-// --------------------
-pub struct IntoIter<T, const N: usize>(ChunkList<T, N>);
+impl std::error::Error for ChunkListError {}
 
-impl<T, const N: usize> ChunkList<T, N> {
-    pub fn into_iter(self) -> IntoIter<T, N> {
-        IntoIter(self)
-    }
+/// Broken invariant reported by `ChunkList::check_invariants`.
+#[cfg(any(test, feature = "check-invariants"))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// `elements_count` differs from the sum of chunk lengths.
+    ElementsCount { stored: usize, counted: usize },
+    /// A chunk which is neither the first nor the last one holds no elements.
+    EmptyChunk { chunk_i: usize },
+    ChunkOverflow { chunk_i: usize, len: usize },
+    /// The ring buffer of a chunk wraps around, so it can't be viewed as a slice.
+    NonContiguousChunk { chunk_i: usize },
+    NonEmptySpareChunk { spare_i: usize, len: usize },
+    /// The chunk index is marked valid but disagrees with the chunk lengths.
+    ChunkIndex { chunk_i: usize, indexed: usize, counted: usize },
 }
 
-impl<T, const N: usize> Iterator for IntoIter<T, N> {
-    type Item = T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let front_chunk = self.0.chunks.front_mut()?;
-        let value = front_chunk.pop_front();
-        if front_chunk.is_empty() {
-            self.0.chunks.pop_front();
+#[cfg(any(test, feature = "check-invariants"))]
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantViolation::ElementsCount { stored, counted } => {
+                write!(f, "elements count is {} but chunks hold {} elements", stored, counted)
+            }
+            InvariantViolation::EmptyChunk { chunk_i } => write!(f, "interior chunk {} is empty", chunk_i),
+            InvariantViolation::ChunkOverflow { chunk_i, len } => {
+                write!(f, "chunk {} holds {} elements, more than its capacity", chunk_i, len)
+            }
+            InvariantViolation::NonContiguousChunk { chunk_i } => write!(f, "chunk {} is not contiguous", chunk_i),
+            InvariantViolation::NonEmptySpareChunk { spare_i, len } => {
+                write!(f, "spare chunk {} holds {} elements", spare_i, len)
+            }
+            InvariantViolation::ChunkIndex { chunk_i, indexed, counted } => {
+                write!(f, "chunk index has {} elements up to chunk {} but chunks hold {}", indexed, chunk_i, counted)
+            }
         }
-        value
     }
 }
 
-// impl<T, const N: usize> Iterator for ChunkList<T, N> {
-//     type Item = T;
-// }
-
-// --------------------
-// ITER
-// --------------------
-pub struct Iter<'a, T, const N: usize> {
-    chunk_list: &'a ChunkList<T, N>,
-    chunk_i: usize,
-    element_i: usize,
+/// Returned by `ChunkList::heap_size`, all sizes are in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HeapSize {
+    /// Chunk headers in the spine and in the pool of spare chunks.
+    pub spine: usize,
+    /// Element buffers of all chunks, including pooled ones.
+    pub chunk_buffers: usize,
+    /// Part of `chunk_buffers` which holds no elements.
+    pub wasted: usize,
 }
 
-impl<T, const N: usize> ChunkList<T, N> {
-    pub fn iter(&self) -> Iter<T, N> {
-        Iter { chunk_list: self, chunk_i: 0, element_i: 0} 
+impl HeapSize {
+    pub fn total(&self) -> usize {
+        self.spine + self.chunk_buffers
     }
 }
 
-impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
-    type Item = &'a T;
-    fn next(&mut self) -> Option<Self::Item> {
-        let chunk = self.chunk_list.chunks.get(self.chunk_i)?;
-        let value = match chunk.get(self.element_i) {
-            None => {
-                self.chunk_i += 1;
-                self.element_i = 0;
-                self.next()
+/// Returned by `ChunkList::occupancy_stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OccupancyStats {
+    /// Fewest elements in a chunk.
+    pub min: usize,
+    /// Most elements in a chunk.
+    pub max: usize,
+    /// Average number of elements in a chunk.
+    pub average: f64,
+    /// `histogram[len]` is how many chunks hold `len` elements, it has N + 1 entries.
+    pub histogram: Vec<usize>,
+}
+
+/// `ChunkList` and `DynChunkList` share this type and all algorithms which don't construct
+/// a list from scratch, they only differ in where the chunk size N comes from.
+pub struct ChunkListBase<T, C> {
+    chunks: VecDeque<Chunk<T, C>>,
+    elements_count: usize,
+    chunk_size: C,
+    /// Empty preallocated chunks which are used before allocating new ones.
+    spare_chunks: Vec<Chunk<T, C>>,
+    /// How many emptied chunks are kept in `spare_chunks` for reuse.
+    chunk_pool_limit: usize,
+    chunk_policy: &'static dyn ChunkPolicy,
+    /// Chunks shorter than this after a removal are merged with or refilled from a neighbour.
+    min_chunk_len: usize,
+    chunk_index: Option<ChunkIndex>,
+}
+
+pub type ChunkList<T, const N: usize> = ChunkListBase<T, Const<N>>;
+
+/// Same as `ChunkList` but the chunk size is given to the constructor,
+/// for when it is only known at runtime.
+pub type DynChunkList<T> = ChunkListBase<T, usize>;
+
+/// Fenwick tree over chunk lengths which resolves element indices in O(log chunks).
+/// `tree[k - 1]` holds the total length of chunks `k - lowest_bit(k)..k`.
+#[derive(Clone)]
+struct ChunkIndex {
+    tree: Vec<usize>,
+    /// Cleared by changes which the tree can't follow cheaply, like adding a front chunk.
+    valid: bool,
+}
+
+fn lowest_bit(k: usize) -> usize {
+    k & k.wrapping_neg()
+}
+
+impl ChunkIndex {
+    fn new<T, C: Capacity>(chunks: &VecDeque<Chunk<T, C>>) -> Self {
+        let mut tree: Vec<usize> = chunks.iter().map(Chunk::len).collect();
+        for k in 1..=tree.len() {
+            let parent = k + lowest_bit(k);
+            if parent <= tree.len() {
+                tree[parent - 1] += tree[k - 1];
             }
-            Some(value) => Some(value),
-        };
-        self.element_i += 1;
-        if self.element_i >= chunk.len() {
-            self.element_i = 0;
-            self.chunk_i += 1;
         }
-        value
+        ChunkIndex { tree, valid: true }
     }
-}
 
+    /// Total length of the first `count` chunks.
+    fn prefix_len(&self, mut count: usize) -> usize {
+        let mut len = 0;
+        while count > 0 {
+            len += self.tree[count - 1];
+            count -= lowest_bit(count);
+        }
+        len
+    }
 
-#[cfg(test)]
-mod test {
-    use super::ChunkList;
-    
-    #[test]
-    fn push_pop_front() {
-        let mut list = ChunkList::<i32, 2>::new();
-        list.push_front(1);
-        list.push_front(2);
-        list.push_front(3);
-        assert_eq!(list.pop_front(), Some(3));
-        assert_eq!(list.pop_front(), Some(2));
-        assert_eq!(list.pop_front(), Some(1));
-        assert_eq!(list.pop_front(), None);
+    fn add(&mut self, chunk_i: usize, delta: isize) {
+        let mut k = chunk_i + 1;
+        while k <= self.tree.len() {
+            self.tree[k - 1] = self.tree[k - 1].wrapping_add_signed(delta);
+            k += lowest_bit(k);
+        }
     }
 
-    #[test]
-    fn peek() {
-        let mut list = ChunkList::<i32, 2>::new();
-        assert_eq!(list.get(0), None);
-        list.push_front(3);
-        list.push_front(2);
-        list.push_front(1);
-        assert_eq!(list.get(0), Some(&1));
+    fn push(&mut self, len: usize) {
+        let k = self.tree.len() + 1;
+        let covered = self.prefix_len(k - 1) - self.prefix_len(k - lowest_bit(k));
+        self.tree.push(covered + len);
     }
 
-    #[test]
-    fn into_iter() {
-        let mut list = ChunkList::<i32, 2>::new();
-        list.push_front(1);
-        list.push_front(2);
-        list.push_front(3);
+    fn pop(&mut self) {
+        self.tree.pop();
+    }
 
-        let mut iter = list.into_iter();
-        assert_eq!(iter.next(), Some(3));
-        assert_eq!(iter.next(), Some(2));
-        assert_eq!(iter.next(), Some(1));
-        assert_eq!(iter.next(), None);
+    /// Returns (chunk index, index inside the chunk), `i` should be less than the total length.
+    fn locate(&self, i: usize) -> (usize, usize) {
+        let (mut count, mut rest) = (0, i);
+        let mut step = match self.tree.len() {
+            0 => 0,
+            len => 1 << len.ilog2(),
+        };
+        while step > 0 {
+            if count + step <= self.tree.len() && self.tree[count + step - 1] <= rest {
+                count += step;
+                rest -= self.tree[count - 1];
+            }
+            step /= 2;
+        }
+        (count, rest)
     }
+}
 
-    #[test]
-    fn iter() {
-        let mut list = ChunkList::<i32, 2>::new();
-        list.push_front(1);
-        list.push_front(2);
-        list.push_front(3);
+const DEFAULT_CHUNK_POOL_LIMIT: usize = 4;
 
-        let mut iter = list.iter();
-        assert_eq!(iter.next(), Some(&3));
-        assert_eq!(iter.next(), Some(&2));
-        assert_eq!(iter.next(), Some(&1));
-        assert_eq!(iter.next(), None);
+/// Decides how a `ChunkList` splits, merges and allocates its chunks.
+/// `capacity` is the chunk size N of the list.
+pub trait ChunkPolicy: Sync {
+    /// How many elements stay in a full chunk when an insertion splits it,
+    /// the rest is moved to a new chunk. Clamped so that both parts have room.
+    fn split_at(&self, capacity: usize) -> usize {
+        capacity / 2
     }
 
-    #[test]
-    fn erase() {
-        let mut list = ChunkList::<i32, 2>::new();
-        list.push_front(1);
-        list.push_front(2);
-        list.push_front(3);
-        list.clear();
-        assert_eq!(list.get(0), None);
+    /// Whether neighbouring chunks are merged after a removal, they are merged
+    /// only if all their elements fit into one chunk.
+    fn should_merge(&self, _left_len: usize, _right_len: usize, _capacity: usize) -> bool {
+        false
     }
 
-    #[test]
-    fn elements_count() {
-        let mut list = ChunkList::<i32, 2>::new();
-        assert_eq!(list.elements_count(), 0);
+    /// Buffer size of newly allocated chunks, buffers are doubled up to `capacity` as chunks fill.
+    fn initial_capacity(&self, capacity: usize) -> usize {
+        capacity
+    }
+}
+
+/// Allocates full chunks, splits them in halves and never merges them.
+pub struct FixedChunkPolicy;
+
+impl ChunkPolicy for FixedChunkPolicy {}
+
+/// Starts chunk buffers at `initial_capacity` and grows them geometrically up to N,
+/// which saves memory for lists that are mostly small.
+pub struct GrowingChunkPolicy {
+    pub initial_capacity: usize,
+}
+
+impl ChunkPolicy for GrowingChunkPolicy {
+    fn initial_capacity(&self, _capacity: usize) -> usize {
+        self.initial_capacity
+    }
+}
+
+impl<T, const N: usize> Default for ChunkList<T, N> {
+    fn default() -> Self {
+        ChunkList::new()
+    }
+}
+
+impl<T: Clone, C: Capacity> Clone for ChunkListBase<T, C> {
+    fn clone(&self) -> Self {
+        ChunkListBase {
+            chunks: self.chunks.clone(),
+            elements_count: self.elements_count,
+            chunk_size: self.chunk_size,
+            spare_chunks: Vec::new(),
+            chunk_pool_limit: self.chunk_pool_limit,
+            chunk_policy: self.chunk_policy,
+            min_chunk_len: self.min_chunk_len,
+            chunk_index: self.chunk_index.clone(),
+        }
+    }
+
+    /// Reuses already allocated chunks of `self` if it has the chunk size of `source`.
+    fn clone_from(&mut self, source: &Self) {
+        if self.chunk_size.get() != source.chunk_size.get() {
+            self.chunks.clear();
+            self.spare_chunks.clear();
+        }
+        self.chunk_size = source.chunk_size;
+        self.chunk_pool_limit = source.chunk_pool_limit;
+        self.spare_chunks.truncate(self.chunk_pool_limit);
+        self.chunk_policy = source.chunk_policy;
+        self.min_chunk_len = source.min_chunk_len;
+        self.chunks.truncate(source.chunks.len());
+        for (chunk, source_chunk) in self.chunks.iter_mut().zip(&source.chunks) {
+            chunk.clone_from(source_chunk);
+        }
+        let cloned = self.chunks.len();
+        self.chunks.extend(source.chunks.iter().skip(cloned).cloned());
+        self.elements_count = source.elements_count;
+        self.chunk_index.clone_from(&source.chunk_index);
+    }
+}
+
+impl<T: fmt::Debug, C: Capacity> fmt::Debug for ChunkListBase<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> ChunkList<T, N> {
+    /// Fails to compile for `N == 0`.
+    pub const fn new() -> Self {
+        const { assert!(N > 0, "chunk size should be > 0") }
+        ChunkListBase::with_chunk_size(Const)
+    }
+
+    /// Constructs an empty list with chunks preallocated for at least `capacity` elements.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut chunk_list = Self::new();
+        chunk_list.reserve(capacity);
+        chunk_list
+    }
+}
+
+impl<T> DynChunkList<T> {
+    pub fn new(chunk_size: usize) -> Self {
+        if chunk_size == 0 {
+            panic!("chunk size should be > 0")
+        }
+        ChunkListBase::with_chunk_size(chunk_size)
+    }
+
+    pub fn try_new(chunk_size: usize) -> Result<Self, ChunkListError> {
+        match chunk_size {
+            0 => Err(ChunkListError::ZeroChunkSize),
+            _ => Ok(DynChunkList::new(chunk_size)),
+        }
+    }
+
+    /// Constructs an empty list with chunks preallocated for at least `capacity` elements.
+    pub fn with_capacity(chunk_size: usize, capacity: usize) -> Self {
+        let mut chunk_list = Self::new(chunk_size);
+        chunk_list.reserve(capacity);
+        chunk_list
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    const fn with_chunk_size(chunk_size: C) -> Self {
+        ChunkListBase {
+            chunks: VecDeque::new(),
+            elements_count: 0,
+            chunk_size,
+            spare_chunks: Vec::new(),
+            chunk_pool_limit: DEFAULT_CHUNK_POOL_LIMIT,
+            chunk_policy: &FixedChunkPolicy,
+            min_chunk_len: 0,
+            chunk_index: None,
+        }
+    }
+
+    /// Preallocates chunks so that pushing `additional` elements to the back doesn't allocate.
+    pub fn reserve(&mut self, additional: usize) {
+        let chunk_size = self.chunk_size.get();
+        let spare = self.chunks.back().map_or(0, |chunk| chunk_size - chunk.len());
+        let needed_chunks = additional.saturating_sub(spare).div_ceil(chunk_size);
+        self.chunks.reserve(needed_chunks);
+        if let Some(index) = &mut self.chunk_index {
+            index.tree.reserve(needed_chunks);
+        }
+        let missing = needed_chunks.saturating_sub(self.spare_chunks.len());
+        self.spare_chunks.reserve(missing);
+        self.spare_chunks.extend((0..missing).map(|_| Chunk::with_capacity(self.chunk_size, chunk_size)));
+    }
+
+    /// Same as `reserve`, but returns an error instead of aborting when an allocation fails.
+    /// Chunks allocated before the failure are kept as spare chunks.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        let chunk_size = self.chunk_size.get();
+        let spare = self.chunks.back().map_or(0, |chunk| chunk_size - chunk.len());
+        if let Some(back) = self.chunks.back_mut() {
+            back.try_reserve_exact(additional.min(spare))?;
+        }
+        let needed_chunks = additional.saturating_sub(spare).div_ceil(chunk_size);
+        self.chunks.try_reserve(needed_chunks)?;
+        if let Some(index) = &mut self.chunk_index {
+            index.tree.try_reserve(needed_chunks)?;
+        }
+        for chunk in self.spare_chunks.iter_mut().rev().take(needed_chunks) {
+            chunk.elements.try_reserve_exact(chunk_size)?;
+        }
+        let missing = needed_chunks.saturating_sub(self.spare_chunks.len());
+        self.spare_chunks.try_reserve(missing)?;
+        for _ in 0..missing {
+            self.spare_chunks.push(Chunk::try_with_capacity(self.chunk_size, chunk_size)?);
+        }
+        Ok(())
+    }
+
+    /// Total number of element slots in allocated chunks, including spare ones.
+    pub fn capacity(&self) -> usize {
+        self.chunks.iter().chain(&self.spare_chunks).map(|chunk| chunk.elements.capacity()).sum()
+    }
+
+    /// Number of element slots that are allocated but not used.
+    pub fn spare_capacity(&self) -> usize {
+        self.capacity() - self.elements_count
+    }
+
+    /// Maximum number of elements in one chunk.
+    pub fn chunk_capacity(&self) -> usize {
+        self.chunk_size.get()
+    }
+
+    /// Frees spare chunks and unused capacity of the spine and of every chunk.
+    /// Chunks that were shrunk reallocate when they get pushed to again.
+    pub fn shrink_to_fit(&mut self) {
+        self.spare_chunks = Vec::new();
+        self.chunks.shrink_to_fit();
+        for chunk in self.chunks.iter_mut() {
+            chunk.shrink_to_fit();
+        }
+    }
+
+    fn new_chunk(&mut self) -> Chunk<T, C> {
+        match self.spare_chunks.pop() {
+            Some(chunk) => chunk,
+            None => {
+                let chunk_size = self.chunk_size.get();
+                Chunk::with_capacity(self.chunk_size, self.chunk_policy.initial_capacity(chunk_size).clamp(1, chunk_size))
+            }
+        }
+    }
+
+    pub fn chunk_policy(&self) -> &'static dyn ChunkPolicy {
+        self.chunk_policy
+    }
+
+    /// Existing chunks are left as they are, the policy applies to later operations.
+    pub fn set_chunk_policy(&mut self, policy: &'static dyn ChunkPolicy) {
+        self.chunk_policy = policy;
+    }
+
+    /// Keeps a Fenwick tree over chunk lengths so that `get`, `insert`, `remove` and other
+    /// lookups by index take O(log chunks) instead of scanning chunks. Pushing and
+    /// popping at the back and edits inside a chunk update it in O(log chunks), bulk
+    /// operations rebuild it. Changes which shift chunks at the front, like `push_front`
+    /// adding a chunk, mark it stale and it is rebuilt by the next mutable lookup.
+    pub fn set_chunk_index(&mut self, enabled: bool) {
+        self.chunk_index = enabled.then(|| ChunkIndex::new(&self.chunks));
+    }
+
+    pub fn has_chunk_index(&self) -> bool {
+        self.chunk_index.is_some()
+    }
+
+    fn valid_chunk_index(&mut self) -> Option<&mut ChunkIndex> {
+        self.chunk_index.as_mut().filter(|index| index.valid)
+    }
+
+    fn invalidate_chunk_index(&mut self) {
+        if let Some(index) = &mut self.chunk_index {
+            index.valid = false;
+        }
+    }
+
+    /// Rebuilds the index if it is enabled, for operations which change many chunks anyway.
+    fn update_chunk_index(&mut self) {
+        if self.chunk_index.is_some() {
+            self.chunk_index = Some(ChunkIndex::new(&self.chunks));
+        }
+    }
+
+    /// Rebuilds the index if it is enabled but stale.
+    fn refresh_chunk_index(&mut self) {
+        if self.chunk_index.as_ref().is_some_and(|index| !index.valid) {
+            self.update_chunk_index();
+        }
+    }
+
+    fn chunk_index_add(&mut self, chunk_i: usize, delta: isize) {
+        if let Some(index) = self.valid_chunk_index() {
+            index.add(chunk_i, delta);
+        }
+    }
+
+    /// Updates the index after elements were only added at the back,
+    /// given the number of chunks and the length of the back chunk before.
+    fn chunk_index_grown_back(&mut self, chunks_count: usize, back_len: usize) {
+        let Some(index) = self.chunk_index.as_mut().filter(|index| index.valid) else {
+            return
+        };
+        if chunks_count > 0 {
+            index.add(chunks_count - 1, (self.chunks[chunks_count - 1].len() - back_len) as isize);
+        }
+        for chunk in self.chunks.range(chunks_count..) {
+            index.push(chunk.len());
+        }
+    }
+
+    /// Minimum part of N that chunks are kept filled to by `remove`, `drain` and `retain`.
+    pub fn min_fill_factor(&self) -> f64 {
+        self.min_chunk_len as f64 / self.chunk_size.get() as f64
+    }
+
+    /// Sets the minimum fill factor, 0 disables rebalancing. Chunks can't all be kept
+    /// more than half full, so the factor should be within `0.0..=0.5`.
+    /// Existing chunks are left as they are until the next removal.
+    pub fn set_min_fill_factor(&mut self, factor: f64) {
+        if !(0.0..=0.5).contains(&factor) {
+            panic!("fill factor (is {}) should be within 0.0..=0.5", factor)
+        }
+        self.min_chunk_len = (factor * self.chunk_size.get() as f64).ceil() as usize;
+    }
+
+    /// Merges an under-filled chunk with a neighbour or moves enough elements from it,
+    /// returns the index of the chunk which holds the elements of the chunk at `chunk_i`.
+    fn rebalance_chunk(&mut self, chunk_i: usize) -> usize {
+        if self.chunks[chunk_i].len() >= self.min_chunk_len || self.chunks.len() < 2 {
+            return chunk_i
+        }
+        self.invalidate_chunk_index();
+        let left_i = if chunk_i + 1 < self.chunks.len() { chunk_i } else { chunk_i - 1 };
+        let mut range = self.chunks.range_mut(left_i..=left_i + 1);
+        let (left, right) = (range.next().unwrap(), range.next_back().unwrap());
+        if left.len() + right.len() <= self.chunk_size.get() {
+            left.append(right);
+            let chunk = self.remove_chunk(left_i + 1);
+            self.recycle_chunk(chunk);
+            return left_i
+        }
+        // The neighbour has more than N - min_chunk_len elements, it stays filled enough.
+        if chunk_i == left_i {
+            let taken = self.min_chunk_len - left.len();
+            left.extend(right.elements.drain(..taken));
+        }
+        else {
+            let taken = self.min_chunk_len - right.len();
+            let start = left.len() - taken;
+            for value in left.elements.drain(start..).rev() {
+                right.push_front(value);
+            }
+        }
+        chunk_i
+    }
+
+    /// Rebalances all under-filled chunks in one pass.
+    fn rebalance(&mut self) {
+        if self.min_chunk_len == 0 {
+            return
+        }
+        let mut chunk_i = 0;
+        while chunk_i < self.chunks.len() {
+            let chunks_count = self.chunks.len();
+            let merged_i = self.rebalance_chunk(chunk_i);
+            // A merged chunk can still be under-filled.
+            chunk_i = if self.chunks.len() < chunks_count && self.chunks[merged_i].len() < self.min_chunk_len {
+                merged_i
+            } else {
+                merged_i + 1
+            };
+        }
+    }
+
+    /// Merges the chunk with the next one if the policy asks for it and they fit into one chunk.
+    fn merge_with_next(&mut self, chunk_i: usize) -> bool {
+        if chunk_i + 1 >= self.chunks.len() {
+            return false
+        }
+        let (left_len, right_len) = (self.chunks[chunk_i].len(), self.chunks[chunk_i + 1].len());
+        if left_len + right_len > self.chunk_size.get() || !self.chunk_policy.should_merge(left_len, right_len, self.chunk_size.get()) {
+            return false
+        }
+        let mut right = self.remove_chunk(chunk_i + 1).unwrap();
+        self.chunks[chunk_i].append(&mut right);
+        self.invalidate_chunk_index();
+        self.recycle_chunk(Some(right));
+        true
+    }
+
+    /// Maximum number of emptied chunks kept for reuse instead of being freed.
+    pub fn chunk_pool_limit(&self) -> usize {
+        self.chunk_pool_limit
+    }
+
+    /// Sets how many emptied chunks are kept for reuse, extra pooled chunks are freed.
+    pub fn set_chunk_pool_limit(&mut self, limit: usize) {
+        self.chunk_pool_limit = limit;
+        self.spare_chunks.truncate(limit);
+    }
+
+    /// Puts a chunk removed from the spine into the pool if there is room for it.
+    fn recycle_chunk(&mut self, chunk: Option<Chunk<T, C>>) {
+        if let Some(chunk) = chunk {
+            recycle_chunk(&mut self.spare_chunks, self.chunk_pool_limit, chunk);
+        }
+    }
+
+    /// Removes empty chunks from the spine, pooling them.
+    fn remove_empty_chunks(&mut self) {
+        self.invalidate_chunk_index();
+        let (pool, limit) = (&mut self.spare_chunks, self.chunk_pool_limit);
+        self.chunks.retain_mut(|chunk| {
+            if !chunk.is_empty() {
+                return true
+            }
+            let chunk_size = chunk.chunk_size;
+            recycle_chunk(pool, limit, mem::replace(chunk, Chunk { elements: VecDeque::new(), chunk_size }));
+            false
+        });
+    }
+
+}
+
+impl<T, const N: usize> ChunkList<T, N> {
+    /// Constructs the container with count copies of elements with value.
+    pub fn new_filled(count: usize, value: &T) -> Self
+    where T: Clone {
+        let mut chunk_list = Self::new();
+        for _ in 0..count {
+            chunk_list.push_back(value.clone())
+        }
+        chunk_list
+    }
+
+    /// Constructs the container with `count` elements, the element at position i is `f(i)`.
+    /// Chunks are allocated full and filled directly.
+    pub fn from_fn(count: usize, mut f: impl FnMut(usize) -> T) -> Self {
+        let mut chunk_list = Self::new();
+        chunk_list.chunks.reserve_exact(count.div_ceil(N));
+        for start in (0..count).step_by(N) {
+            let mut chunk = Chunk::with_capacity(Const, N.min(count - start));
+            chunk.elements.extend((start..count.min(start + N)).map(&mut f));
+            chunk_list.chunks.push_back(chunk);
+        }
+        chunk_list.elements_count = count;
+        chunk_list
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn add_new_chunk_front(&mut self) -> &mut Chunk<T, C> {
+        self.invalidate_chunk_index();
+        let chunk = self.new_chunk();
+        self.chunks.push_front(chunk);
+        self.chunks.front_mut().unwrap()
+    }
+
+    pub fn add_new_chunk_back(&mut self) -> &mut Chunk<T, C> {
+        self.invalidate_chunk_index();
+        self.push_new_chunk_back()
+    }
+
+    /// Same as `add_new_chunk_back` for callers which update the chunk index themselves.
+    fn push_new_chunk_back(&mut self) -> &mut Chunk<T, C> {
+        let chunk = self.new_chunk();
+        self.chunks.push_back(chunk);
+        self.chunks.back_mut().unwrap()
+    }
+
+    pub fn remove_chunk(&mut self, i: usize) -> Option<Chunk<T, C>> {
+        self.invalidate_chunk_index();
+        self.chunks.remove(i)
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let (chunks_count, back_len) = (self.chunks.len(), self.chunks.back().map_or(0, Chunk::len));
+        match self.chunks.back_mut() {
+            Some(back) if !back.is_full() => back.push_back(value),
+            _ => self.push_new_chunk_back().push_back(value),
+        };
+        self.chunk_index_grown_back(chunks_count, back_len);
+        self.elements_count += 1
+    }
+
+    /// Pushes the value only if the room for it could be allocated, otherwise the value is dropped.
+    pub fn try_push_back(&mut self, value: T) -> Result<(), TryReserveError> {
+        self.try_reserve(1)?;
+        let (chunks_count, back_len) = (self.chunks.len(), self.chunks.back().map_or(0, Chunk::len));
+        if self.chunks.back().is_none_or(Chunk::is_full) {
+            self.push_new_chunk_back();
+        }
+        if let Err(error) = self.chunks.back_mut().unwrap().try_push_back(value) {
+            if self.chunks.len() > chunks_count {
+                let chunk = self.chunks.pop_back();
+                self.recycle_chunk(chunk);
+            }
+            return Err(error)
+        }
+        self.chunk_index_grown_back(chunks_count, back_len);
+        self.elements_count += 1;
+        Ok(())
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let not_full_chunk = match self.chunks.front_mut() {
+            None => self.add_new_chunk_front(),
+            Some(chunk) => {
+                if chunk.is_full() {
+                    self.add_new_chunk_front()
+                } else {
+                    chunk
+                }
+            },
+        };
+        not_full_chunk.push_front(value);
+        self.chunk_index_add(0, 1);
+        self.elements_count += 1;
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let chunk = self.chunks.back_mut()?;
+        let value = chunk.pop_back().unwrap();
+        if chunk.is_empty() {
+            let chunk = self.chunks.pop_back();
+            self.recycle_chunk(chunk);
+            if let Some(index) = self.valid_chunk_index() {
+                index.pop();
+            }
+        }
+        else {
+            self.chunk_index_add(self.chunks.len() - 1, -1);
+        }
+        self.elements_count -= 1;
+        Some(value)
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        self.remove(0)
+    }
+
+    pub fn chunks_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn elements_count(&self) -> usize {
+        self.elements_count
+    }
+
+    /// Deletes all elements in the list.
+    pub fn clear(&mut self)
+    where T: PartialEq {
+        while let Some(chunk) = self.chunks.pop_back() {
+            self.recycle_chunk(Some(chunk));
+        }
+        self.elements_count = 0;
+        self.update_chunk_index();
+    }
+
+    /// O(chunks), use `Cursor::seek` for sequential or nearby lookups.
+    /// The position from `locate` is in bounds, so only the slice check of the chunk remains.
+    pub fn get(&self, i: usize) -> Option<&T> {
+        let (chunk_i, element_i) = self.locate(i)?;
+        self.chunks.get(chunk_i)?.as_slice().get(element_i)
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        let (chunk_i, element_i) = self.locate_mut(i)?;
+        self.chunks.get_mut(chunk_i)?.as_mut_slice().get_mut(element_i)
+    }
+
+    pub fn try_get(&self, i: usize) -> Result<&T, ChunkListError> {
+        let len = self.elements_count;
+        self.get(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
+    }
+
+    pub fn try_get_mut(&mut self, i: usize) -> Result<&mut T, ChunkListError> {
+        let len = self.elements_count;
+        self.get_mut(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.chunks.front()?.as_slice().first()
+    }
+
+    pub fn front_mut(&mut self) -> Option<&mut T> {
+        self.chunks.front_mut()?.as_mut_slice().first_mut()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.chunks.back()?.as_slice().last()
+    }
+
+    pub fn back_mut(&mut self) -> Option<&mut T> {
+        self.chunks.back_mut()?.as_mut_slice().last_mut()
+    }
+
+    /// Returns (chunk index, index inside the chunk) of the i-th element.
+    fn locate(&self, i: usize) -> Option<(usize, usize)> {
+        if i >= self.elements_count {
+            return None
+        }
+        if let Some(index) = self.chunk_index.as_ref().filter(|index| index.valid) {
+            let located = index.locate(i);
+            debug_assert_eq!(Some(located), self.locate_by_scan(i));
+            return Some(located)
+        }
+        self.locate_by_scan(i)
+    }
+
+    /// Same as `locate` but rebuilds a stale chunk index first,
+    /// unless the element is in the front chunk anyway.
+    fn locate_mut(&mut self, i: usize) -> Option<(usize, usize)> {
+        if self.chunks.front().is_some_and(|front| i >= front.len()) {
+            self.refresh_chunk_index();
+        }
+        self.locate(i)
+    }
+
+    fn locate_by_scan(&self, i: usize) -> Option<(usize, usize)> {
+        let mut count = 0;
+        for (chunk_i, chunk) in self.chunks.iter().enumerate() {
+            if i < count + chunk.len() {
+                return Some((chunk_i, i - count))
+            }
+            count += chunk.len();
+        }
+        None
+    }
+
+    pub fn remove(&mut self, i: usize) -> Option<T> {
+        let (chunk_i, element_i) = self.locate_mut(i)?;
+        let value = self.chunks[chunk_i].remove(element_i);
+        self.chunk_index_add(chunk_i, -1);
+        if self.chunks[chunk_i].is_empty() {
+            let chunk = self.remove_chunk(chunk_i);
+            self.recycle_chunk(chunk);
+        }
+        else if !(self.merge_with_next(chunk_i) || chunk_i > 0 && self.merge_with_next(chunk_i - 1)) {
+            self.rebalance_chunk(chunk_i);
+        }
+        self.elements_count -= 1;
+        value
+    }
+
+    pub fn try_remove(&mut self, i: usize) -> Result<T, ChunkListError> {
+        let len = self.elements_count;
+        self.remove(i).ok_or(ChunkListError::IndexOutOfBounds { index: i, len })
+    }
+
+    /// Keeps only the elements for which `f` returns true.
+    pub fn retain(&mut self, mut f: impl FnMut(&T) -> bool) {
+        self.retain_mut(|value| f(value))
+    }
+
+    pub fn retain_mut(&mut self, mut f: impl FnMut(&mut T) -> bool) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.elements.retain_mut(&mut f);
+        }
+        self.remove_empty_chunks();
+        self.rebalance();
+        self.elements_count = self.chunks.iter().map(Chunk::len).sum();
+        self.update_chunk_index();
+    }
+
+    /// Moves elements starting from position i into a new list.
+    /// Only the chunk containing position i gets split.
+    ///
+    /// Panics if `i > elements_count`.
+    pub fn split_off(&mut self, i: usize) -> Self {
+        if i > self.elements_count {
+            panic!("split index (is {}) should be <= len (is {})", i, self.elements_count)
+        }
+        let mut tail = self.empty_like();
+        let Some((chunk_i, element_i)) = self.locate(i) else {
+            return tail
+        };
+        if element_i == 0 {
+            tail.chunks = self.chunks.split_off(chunk_i);
+        }
+        else {
+            tail.chunks = self.chunks.split_off(chunk_i + 1);
+            tail.add_new_chunk_front().extend(self.chunks[chunk_i].elements.drain(element_i..));
+        }
+        tail.elements_count = self.elements_count - i;
+        self.elements_count = i;
+        self.update_chunk_index();
+        tail.update_chunk_index();
+        tail
+    }
+
+    /// Empty list with the same chunk policy, fill factor, pool limit and chunk index setting.
+    fn empty_like(&self) -> Self {
+        ChunkListBase {
+            chunk_pool_limit: self.chunk_pool_limit,
+            chunk_policy: self.chunk_policy,
+            min_chunk_len: self.min_chunk_len,
+            chunk_index: self.chunk_index.as_ref().map(|_| ChunkIndex::new(&VecDeque::<Chunk<T, C>>::new())),
+            ..Self::with_chunk_size(self.chunk_size)
+        }
+    }
+
+    /// Removes the last k elements, or all of them if there are fewer, and returns them.
+    /// Whole chunks are moved, only the chunk at the boundary gets split.
+    pub fn pop_back_n(&mut self, k: usize) -> Self {
+        self.split_off(self.elements_count.saturating_sub(k))
+    }
+
+    /// Removes the first k elements, or all of them if there are fewer, and returns them.
+    pub fn pop_front_n(&mut self, k: usize) -> Self {
+        let mut front = self.split_off(k.min(self.elements_count));
+        mem::swap(&mut self.chunks, &mut front.chunks);
+        mem::swap(&mut self.elements_count, &mut front.elements_count);
+        self.update_chunk_index();
+        front.update_chunk_index();
+        front
+    }
+
+    /// Moves all chunks of `other` to the end of `self`, leaving `other` empty.
+    /// The two boundary chunks get merged if their elements fit into one chunk.
+    ///
+    /// Panics if the lists have different chunk sizes.
+    pub fn append(&mut self, other: &mut Self) {
+        if self.chunk_size.get() != other.chunk_size.get() {
+            panic!("chunk sizes should be equal (are {} and {})", self.chunk_size.get(), other.chunk_size.get())
+        }
+        if let (Some(back), Some(front)) = (self.chunks.back_mut(), other.chunks.front_mut()) {
+            if back.len() + front.len() <= self.chunk_size.get() {
+                back.append(front);
+                let front = other.chunks.pop_front();
+                other.recycle_chunk(front);
+            }
+        }
+        self.chunks.append(&mut other.chunks);
+        self.elements_count += other.elements_count;
+        other.elements_count = 0;
+        self.update_chunk_index();
+        other.update_chunk_index();
+    }
+
+    /// Splits the list along chunk boundaries into `k` lists of roughly equal length,
+    /// for example to hand them over to threads. A shard ends before the chunk whose middle
+    /// is past its share of elements, trailing shards are empty if there are fewer chunks than `k`.
+    /// The shards are in order, `from_shards` or `append` glues them back.
+    ///
+    /// Panics if `k == 0`.
+    pub fn split_into_shards(mut self, k: usize) -> Vec<Self> {
+        if k == 0 {
+            panic!("shard count should be > 0")
+        }
+        let total = self.elements_count;
+        let mut chunks = mem::take(&mut self.chunks).into_iter().peekable();
+        let mut taken = 0;
+        let mut shards = Vec::with_capacity(k);
+        for shard_i in 1..=k {
+            let target = total / k * shard_i + total % k * shard_i / k;
+            let mut shard = self.empty_like();
+            while let Some(chunk) = chunks.next_if(|chunk| shard_i == k || 2 * taken + chunk.len() <= 2 * target) {
+                taken += chunk.len();
+                shard.elements_count += chunk.len();
+                shard.chunks.push_back(chunk);
+            }
+            shard.update_chunk_index();
+            shards.push(shard);
+        }
+        shards
+    }
+}
+
+impl<T, const N: usize> ChunkList<T, N> {
+    /// Appends the lists one after another, the inverse of `split_into_shards`.
+    /// The result takes its configuration from the first shard.
+    pub fn from_shards(shards: impl IntoIterator<Item = Self>) -> Self {
+        let mut shards = shards.into_iter();
+        let Some(mut first) = shards.next() else {
+            return ChunkList::new()
+        };
+        let mut chunk_list = first.empty_like();
+        chunk_list.append(&mut first);
+        for mut shard in shards {
+            chunk_list.append(&mut shard);
+        }
+        chunk_list
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+
+    /// Drops elements from the back so that at most `len` are left.
+    pub fn truncate(&mut self, len: usize) {
+        while self.elements_count > len {
+            let back = self.chunks.back_mut().unwrap();
+            let excess = self.elements_count - len;
+            if back.len() <= excess {
+                self.elements_count -= back.len();
+                let back = self.chunks.pop_back();
+                self.recycle_chunk(back);
+            }
+            else {
+                back.elements.truncate(back.len() - excess);
+                self.elements_count = len;
+            }
+        }
+        self.update_chunk_index();
+    }
+
+    /// Drops elements from the front so that at most `len` are left.
+    pub fn truncate_front(&mut self, len: usize) {
+        while self.elements_count > len {
+            let front = self.chunks.front_mut().unwrap();
+            let excess = self.elements_count - len;
+            if front.len() <= excess {
+                self.elements_count -= front.len();
+                let front = self.chunks.pop_front();
+                self.recycle_chunk(front);
+            }
+            else {
+                front.elements.drain(..excess);
+                self.elements_count = len;
+            }
+        }
+        self.update_chunk_index();
+    }
+
+    /// Clones the slice into the spare capacity of the back chunk and then into new chunks.
+    /// `extend_from_copy_slice` is faster for `Copy` types.
+    pub fn extend_from_slice(&mut self, slice: &[T])
+    where T: Clone {
+        self.extend_from_slice_with(slice, |chunk, part| chunk.extend(part.iter().cloned()));
+    }
+
+    /// Same as `extend_from_slice`, but every segment is copied with a single memcpy.
+    pub fn extend_from_copy_slice(&mut self, slice: &[T])
+    where T: Copy {
+        self.extend_from_slice_with(slice, Chunk::extend_copied);
+    }
+
+    /// Splits the slice into segments filling the back chunk and new chunks, `fill` appends one.
+    fn extend_from_slice_with(&mut self, slice: &[T], mut fill: impl FnMut(&mut Chunk<T, C>, &[T])) {
+        let mut rest = slice;
+        let (chunks_count, back_len) = (self.chunks.len(), self.chunks.back().map_or(0, Chunk::len));
+        if let Some(back) = self.chunks.back_mut() {
+            let taken = (self.chunk_size.get() - back.len()).min(rest.len());
+            fill(back, &rest[..taken]);
+            rest = &rest[taken..];
+        }
+        self.chunks.reserve(rest.len().div_ceil(self.chunk_size.get()));
+        for part in rest.chunks(self.chunk_size.get()) {
+            fill(self.push_new_chunk_back(), part);
+        }
+        self.chunk_index_grown_back(chunks_count, back_len);
+        self.elements_count += slice.len();
+    }
+
+    /// Resizes the list to `new_len` elements, filling new slots with clones of `value`.
+    pub fn resize(&mut self, new_len: usize, value: T)
+    where T: Clone {
+        if new_len > self.elements_count {
+            self.extend(std::iter::repeat_n(value, new_len - self.elements_count));
+        }
+        else {
+            self.truncate(new_len);
+        }
+    }
+
+    /// Resizes the list to `new_len` elements, filling new slots with values returned by `f`.
+    pub fn resize_with(&mut self, new_len: usize, f: impl FnMut() -> T) {
+        if new_len > self.elements_count {
+            self.extend(std::iter::repeat_with(f).take(new_len - self.elements_count));
+        }
+        else {
+            self.truncate(new_len);
+        }
+    }
+
+    /// Swaps elements at positions i and j.
+    ///
+    /// Panics if either index is out of bounds.
+    pub fn swap(&mut self, i: usize, j: usize) {
+        let len = self.elements_count;
+        let out_of_bounds = |index| -> ! {
+            panic!("index out of bounds: the len is {} but the index is {}", len, index)
+        };
+        let (chunk_i, element_i) = self.locate(i).unwrap_or_else(|| out_of_bounds(i));
+        let (chunk_j, element_j) = self.locate(j).unwrap_or_else(|| out_of_bounds(j));
+        if chunk_i == chunk_j {
+            self.chunks[chunk_i].elements.swap(element_i, element_j);
+            return
+        }
+        let mut range = self.chunks.range_mut(chunk_i.min(chunk_j)..=chunk_i.max(chunk_j));
+        let (first, last) = (range.next().unwrap(), range.next_back().unwrap());
+        let (element_first, element_last) = if chunk_i < chunk_j {
+            (element_i, element_j)
+        } else {
+            (element_j, element_i)
+        };
+        mem::swap(&mut first.elements[element_first], &mut last.elements[element_last]);
+    }
+
+    /// Removes the element at position i replacing it with the last element.
+    /// Doesn't preserve ordering, but doesn't shift elements either.
+    pub fn swap_remove_back(&mut self, i: usize) -> Option<T> {
+        if i >= self.elements_count {
+            return None
+        }
+        self.swap(i, self.elements_count - 1);
+        self.pop_back()
+    }
+
+    /// Removes the element at position i replacing it with the first element.
+    /// Doesn't preserve ordering, but doesn't shift elements either.
+    pub fn swap_remove_front(&mut self, i: usize) -> Option<T> {
+        if i >= self.elements_count {
+            return None
+        }
+        self.swap(i, 0);
+        self.pop_front()
+    }
+
+    /// Reverses the order of chunks and of elements inside every chunk.
+    pub fn reverse(&mut self) {
+        self.chunks.make_contiguous().reverse();
+        for chunk in self.chunks.iter_mut() {
+            chunk.as_mut_slice().reverse();
+        }
+        self.update_chunk_index();
+    }
+
+    /// Rotates the list so that the first k elements move to the back.
+    /// Whole chunks are moved, only the chunk at position k gets split.
+    ///
+    /// Panics if `k > elements_count`.
+    pub fn rotate_left(&mut self, k: usize) {
+        if k > self.elements_count {
+            panic!("rotation (is {}) should be <= len (is {})", k, self.elements_count)
+        }
+        let mut tail = self.split_off(k);
+        tail.append(self);
+        mem::swap(&mut self.chunks, &mut tail.chunks);
+        self.elements_count = tail.elements_count;
+        self.update_chunk_index();
+    }
+
+    /// Rotates the list so that the last k elements move to the front.
+    ///
+    /// Panics if `k > elements_count`.
+    pub fn rotate_right(&mut self, k: usize) {
+        if k > self.elements_count {
+            panic!("rotation (is {}) should be <= len (is {})", k, self.elements_count)
+        }
+        self.rotate_left(self.elements_count - k);
+    }
+
+    /// Removes consecutive repeated elements.
+    pub fn dedup(&mut self)
+    where T: PartialEq {
+        self.dedup_by(|a, b| a == b)
+    }
+
+    /// Removes consecutive elements that map to the same key.
+    pub fn dedup_by_key<K: PartialEq>(&mut self, mut key: impl FnMut(&mut T) -> K) {
+        self.dedup_by(|a, b| key(a) == key(b))
+    }
+
+    /// Removes consecutive elements for which `same_bucket(element, previous kept element)` is true.
+    pub fn dedup_by(&mut self, mut same_bucket: impl FnMut(&mut T, &mut T) -> bool) {
+        let mut last_kept_chunk_i = None;
+        for chunk_i in 0..self.chunks.len() {
+            if let Some(last_kept_chunk_i) = last_kept_chunk_i {
+                let mut range = self.chunks.range_mut(last_kept_chunk_i..=chunk_i);
+                let (previous, chunk) = (range.next().unwrap(), range.next_back().unwrap());
+                let previous_last = previous.elements.back_mut().unwrap();
+                let duplicates = chunk.elements.iter_mut()
+                    .position(|value| !same_bucket(value, previous_last))
+                    .unwrap_or(chunk.len());
+                chunk.elements.drain(..duplicates);
+            }
+            let chunk = &mut self.chunks[chunk_i];
+            let mut elements = Vec::from(mem::take(&mut chunk.elements));
+            elements.dedup_by(&mut same_bucket);
+            chunk.elements = VecDeque::from(elements);
+            if !chunk.is_empty() {
+                last_kept_chunk_i = Some(chunk_i);
+            }
+        }
+        self.remove_empty_chunks();
+        self.elements_count = self.chunks.iter().map(Chunk::len).sum();
+        self.update_chunk_index();
+    }
+
+    /// Inserts an element at position i, splitting the target chunk in halves if it is full.
+    ///
+    /// Panics if `i > elements_count`.
+    pub fn insert(&mut self, i: usize, value: T) {
+        if i > self.elements_count {
+            panic!("insertion index (is {}) should be <= len (is {})", i, self.elements_count)
+        }
+        if i == self.elements_count {
+            self.push_back(value);
+            return
+        }
+        let (chunk_i, element_i) = self.locate_mut(i).unwrap();
+        self.insert_at(chunk_i, element_i, value);
+    }
+
+    /// Same as `insert`, but returns an error instead of panicking, the value is dropped then.
+    pub fn try_insert(&mut self, i: usize, value: T) -> Result<(), ChunkListError> {
+        if i > self.elements_count {
+            return Err(ChunkListError::IndexOutOfBounds { index: i, len: self.elements_count })
+        }
+        self.insert(i, value);
+        Ok(())
+    }
+
+    /// Inserts an element before the element at (chunk_i, element_i), which may also point
+    /// one past the end of a chunk or at `chunks_count()` to push back.
+    /// Returns the position of the inserted element.
+    fn insert_at(&mut self, chunk_i: usize, element_i: usize, value: T) -> (usize, usize) {
+        if chunk_i == self.chunks.len() {
+            self.push_back(value);
+            return (self.chunks.len() - 1, self.chunks.back().unwrap().len() - 1)
+        }
+        self.elements_count += 1;
+        if element_i == 0 && chunk_i > 0 && !self.chunks[chunk_i - 1].is_full() {
+            self.chunks[chunk_i - 1].push_back(value);
+            self.chunk_index_add(chunk_i - 1, 1);
+            (chunk_i - 1, self.chunks[chunk_i - 1].len() - 1)
+        }
+        else if !self.chunks[chunk_i].is_full() {
+            assert!(self.chunks[chunk_i].insert(element_i, value));
+            self.chunk_index_add(chunk_i, 1);
+            (chunk_i, element_i)
+        }
+        else if element_i == self.chunks[chunk_i].len() {
+            // One past the end of a full chunk, there is nothing to split off.
+            if chunk_i + 1 < self.chunks.len() && !self.chunks[chunk_i + 1].is_full() {
+                assert!(self.chunks[chunk_i + 1].push_front(value));
+                self.chunk_index_add(chunk_i + 1, 1);
+            }
+            else {
+                self.invalidate_chunk_index();
+                let mut chunk = self.new_chunk();
+                assert!(chunk.push_back(value));
+                self.chunks.insert(chunk_i + 1, chunk);
+            }
+            (chunk_i + 1, 0)
+        }
+        else {
+            self.invalidate_chunk_index();
+            // Both parts need room for the new element.
+            let split = match self.chunk_size.get() {
+                1 => 0,
+                chunk_size => self.chunk_policy.split_at(chunk_size).clamp(1, chunk_size - 1),
+            };
+            let mut tail = self.new_chunk();
+            tail.extend(self.chunks[chunk_i].elements.drain(split..));
+            self.chunks.insert(chunk_i + 1, tail);
+            if element_i <= split {
+                assert!(self.chunks[chunk_i].insert(element_i, value));
+                (chunk_i, element_i)
+            }
+            else {
+                assert!(self.chunks[chunk_i + 1].insert(element_i - split, value));
+                (chunk_i + 1, element_i - split)
+            }
+        }
+    }
+}
+
+/// Chunks with less than N capacity left after `shrink_to_fit` are not worth keeping.
+fn recycle_chunk<T, C: Capacity>(pool: &mut Vec<Chunk<T, C>>, limit: usize, mut chunk: Chunk<T, C>) {
+    if pool.len() < limit && chunk.elements.capacity() >= chunk.chunk_size.get() {
+        chunk.elements.clear();
+        pool.push(chunk);
+    }
+}
+
+// --------------------
+// INTO ITER
+// --------------------
+pub struct IntoIter<T, C>(ChunkListBase<T, C>);
+
+impl<T, C: Capacity> IntoIterator for ChunkListBase<T, C> {
+    type Item = T;
+    type IntoIter = IntoIter<T, C>;
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter(self)
+    }
+}
+
+impl<T, C: Capacity> Iterator for IntoIter<T, C> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.0.elements_count, Some(self.0.elements_count))
+    }
+
+    /// Drops whole chunks from the front instead of popping element by element.
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.0.truncate_front(self.0.elements_count.saturating_sub(n));
+        self.0.pop_front()
+    }
+}
+
+impl<T, C: Capacity> DoubleEndedIterator for IntoIter<T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.pop_back()
+    }
+}
+
+impl<T, C: Capacity> ExactSizeIterator for IntoIter<T, C> {}
+
+impl<T, C: Capacity> FusedIterator for IntoIter<T, C> {}
+
+// impl<T, C: Capacity> Iterator for ChunkListBase<T, C> {
+//     type Item = T;
+// }
+
+// --------------------
+// ITER
+// --------------------
+/// Walks over the slice of the current chunk and only touches the spine when crossing chunks.
+pub struct Iter<'a, T, C> {
+    chunks: vec_deque::Iter<'a, Chunk<T, C>>,
+    front: slice::Iter<'a, T>,
+    back: slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T, C: Capacity> Clone for Iter<'a, T, C> {
+    fn clone(&self) -> Self {
+        Iter {
+            chunks: self.chunks.clone(),
+            front: self.front.clone(),
+            back: self.back.clone(),
+            remaining: self.remaining,
+        }
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn iter(&self) -> Iter<'_, T, C> {
+        Iter {
+            chunks: self.chunks.iter(),
+            front: [].iter(),
+            back: [].iter(),
+            remaining: self.elements_count,
+        }
+    }
+
+    /// Iterates over elements in range, chunks before the range are skipped in O(chunks).
+    pub fn range(&self, range: impl RangeBounds<usize>) -> Iter<'_, T, C> {
+        let (start, end) = range_to_indices(range, self.elements_count);
+        if start == end {
+            return Iter {
+                chunks: self.chunks.range(0..0),
+                front: [].iter(),
+                back: [].iter(),
+                remaining: 0,
+            }
+        }
+        let (first_chunk_i, first_element_i) = self.locate(start).unwrap();
+        let (last_chunk_i, last_element_i) = self.locate(end - 1).unwrap();
+        let mut chunks = self.chunks.range(first_chunk_i..=last_chunk_i);
+        let first = chunks.next().unwrap();
+        let (front, back) = if first_chunk_i == last_chunk_i {
+            (first.as_slice()[first_element_i..=last_element_i].iter(), [].iter())
+        } else {
+            let last = chunks.next_back().unwrap();
+            (first.as_slice()[first_element_i..].iter(), last.as_slice()[..=last_element_i].iter())
+        };
+        Iter { chunks, front, back, remaining: end - start }
+    }
+}
+
+impl<'a, T, C: Capacity> IntoIterator for &'a ChunkListBase<T, C> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, C>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, C: Capacity> Iterator for Iter<'a, T, C> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.front.next() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.as_slice().iter(),
+                None => {
+                    let value = self.back.next()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    /// Skips whole chunks by their lengths, so `skip(n)` costs O(chunks) instead of O(n).
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        if n >= self.remaining {
+            self.chunks = Default::default();
+            self.front = Default::default();
+            self.back = Default::default();
+            self.remaining = 0;
+            return None
+        }
+        self.remaining -= n + 1;
+        loop {
+            if n < self.front.len() {
+                return self.front.nth(n)
+            }
+            n -= self.front.len();
+            self.front = Default::default();
+            match self.chunks.next() {
+                Some(chunk) if chunk.len() <= n => n -= chunk.len(),
+                Some(chunk) => self.front = chunk.as_slice().iter(),
+                None => return self.back.nth(n),
+            }
+        }
+    }
+}
+
+impl<'a, T, C: Capacity> DoubleEndedIterator for Iter<'a, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.as_slice().iter(),
+                None => {
+                    let value = self.front.next_back()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, C: Capacity> ExactSizeIterator for Iter<'a, T, C> {}
+
+impl<'a, T, C: Capacity> FusedIterator for Iter<'a, T, C> {}
+
+// --------------------
+// ITER MUT
+// --------------------
+pub struct IterMut<'a, T, C> {
+    chunks: vec_deque::IterMut<'a, Chunk<T, C>>,
+    front: slice::IterMut<'a, T>,
+    back: slice::IterMut<'a, T>,
+    remaining: usize,
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn iter_mut(&mut self) -> IterMut<'_, T, C> {
+        IterMut {
+            chunks: self.chunks.iter_mut(),
+            front: [].iter_mut(),
+            back: [].iter_mut(),
+            remaining: self.elements_count,
+        }
+    }
+
+    /// Mutably iterates over elements in range, chunks before the range are skipped in O(chunks).
+    pub fn range_mut(&mut self, range: impl RangeBounds<usize>) -> IterMut<'_, T, C> {
+        let (start, end) = range_to_indices(range, self.elements_count);
+        if start == end {
+            return IterMut {
+                chunks: self.chunks.range_mut(0..0),
+                front: [].iter_mut(),
+                back: [].iter_mut(),
+                remaining: 0,
+            }
+        }
+        let (first_chunk_i, first_element_i) = self.locate(start).unwrap();
+        let (last_chunk_i, last_element_i) = self.locate(end - 1).unwrap();
+        let mut chunks = self.chunks.range_mut(first_chunk_i..=last_chunk_i);
+        let first = chunks.next().unwrap();
+        let (front, back) = if first_chunk_i == last_chunk_i {
+            (first.as_mut_slice()[first_element_i..=last_element_i].iter_mut(), [].iter_mut())
+        } else {
+            let last = chunks.next_back().unwrap();
+            (first.as_mut_slice()[first_element_i..].iter_mut(), last.as_mut_slice()[..=last_element_i].iter_mut())
+        };
+        IterMut { chunks, front, back, remaining: end - start }
+    }
+}
+
+impl<'a, T, C: Capacity> IntoIterator for &'a mut ChunkListBase<T, C> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T, C>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<'a, T, C: Capacity> Iterator for IterMut<'a, T, C> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.front.next() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.as_mut_slice().iter_mut(),
+                None => {
+                    let value = self.back.next()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+
+    /// Skips whole chunks by their lengths, so `skip(n)` costs O(chunks) instead of O(n).
+    fn nth(&mut self, mut n: usize) -> Option<Self::Item> {
+        if n >= self.remaining {
+            self.chunks = Default::default();
+            self.front = Default::default();
+            self.back = Default::default();
+            self.remaining = 0;
+            return None
+        }
+        self.remaining -= n + 1;
+        loop {
+            if n < self.front.len() {
+                return self.front.nth(n)
+            }
+            n -= self.front.len();
+            self.front = Default::default();
+            match self.chunks.next() {
+                Some(chunk) if chunk.len() <= n => n -= chunk.len(),
+                Some(chunk) => self.front = chunk.as_mut_slice().iter_mut(),
+                None => return self.back.nth(n),
+            }
+        }
+    }
+}
+
+impl<'a, T, C: Capacity> DoubleEndedIterator for IterMut<'a, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.as_mut_slice().iter_mut(),
+                None => {
+                    let value = self.front.next_back()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, C: Capacity> ExactSizeIterator for IterMut<'a, T, C> {}
+
+impl<'a, T, C: Capacity> FusedIterator for IterMut<'a, T, C> {}
+
+// --------------------
+// CHUNKS
+// --------------------
+/// Iterator over the chunks of a list as slices.
+pub struct Chunks<'a, T, C>(vec_deque::Iter<'a, Chunk<T, C>>);
+
+/// Iterator over the chunks of a list as mutable slices.
+pub struct ChunksMut<'a, T, C>(vec_deque::IterMut<'a, Chunk<T, C>>);
+
+/// Consuming iterator over the chunks of a list as vectors.
+pub struct IntoChunks<T, C>(vec_deque::IntoIter<Chunk<T, C>>);
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn chunks(&self) -> Chunks<'_, T, C> {
+        Chunks(self.chunks.iter())
+    }
+
+    pub fn chunks_mut(&mut self) -> ChunksMut<'_, T, C> {
+        ChunksMut(self.chunks.iter_mut())
+    }
+
+    /// Adopts the buffer of `elements` as a new back chunk without moving them. Inputs longer
+    /// than N are split into chunks of N, the buffer is kept by the first one.
+    pub fn push_chunk_back(&mut self, elements: impl Into<Vec<T>>) {
+        let (chunks_count, back_len) = (self.chunks.len(), self.chunks.back().map_or(0, Chunk::len));
+        for chunk in Self::adopt(self.chunk_size, elements.into()) {
+            self.elements_count += chunk.len();
+            self.chunks.push_back(chunk);
+        }
+        self.chunk_index_grown_back(chunks_count, back_len);
+    }
+
+    /// Same as `push_chunk_back` at the front.
+    pub fn push_chunk_front(&mut self, elements: impl Into<Vec<T>>) {
+        let chunks = Self::adopt(self.chunk_size, elements.into());
+        if !chunks.is_empty() {
+            self.invalidate_chunk_index();
+        }
+        for chunk in chunks.into_iter().rev() {
+            self.elements_count += chunk.len();
+            self.chunks.push_front(chunk);
+        }
+    }
+
+    /// Detaches the back chunk and returns it as a vector which reuses its buffer.
+    pub fn pop_chunk_back(&mut self) -> Option<Vec<T>> {
+        while let Some(chunk) = self.chunks.pop_back() {
+            if let Some(index) = self.valid_chunk_index() {
+                index.pop();
+            }
+            if chunk.is_empty() {
+                self.recycle_chunk(Some(chunk));
+                continue
+            }
+            self.elements_count -= chunk.len();
+            return Some(chunk.into_vec())
+        }
+        None
+    }
+
+    /// Same as `pop_chunk_back` at the front.
+    pub fn pop_chunk_front(&mut self) -> Option<Vec<T>> {
+        while let Some(chunk) = self.chunks.pop_front() {
+            self.invalidate_chunk_index();
+            if chunk.is_empty() {
+                self.recycle_chunk(Some(chunk));
+                continue
+            }
+            self.elements_count -= chunk.len();
+            return Some(chunk.into_vec())
+        }
+        None
+    }
+
+    /// Decomposes the list into its chunk buffers and elements count, see `from_raw_parts`.
+    pub fn into_raw_parts(self) -> (Vec<Vec<T>>, usize) {
+        let elements_count = self.elements_count;
+        (self.chunks.into_iter().map(Chunk::into_vec).collect(), elements_count)
+    }
+}
+
+impl<T, const N: usize> ChunkList<T, N> {
+    /// Reassembles a list from chunk buffers, which are adopted as they are. Every chunk must
+    /// hold at most N elements and `elements_count` must match them, empty chunks are dropped.
+    pub fn from_raw_parts(chunks: Vec<Vec<T>>, elements_count: usize) -> Result<Self, ChunkListError> {
+        if let Some((chunk_i, chunk)) = chunks.iter().enumerate().find(|(_, chunk)| chunk.len() > N) {
+            return Err(ChunkListError::ChunkOverflow { chunk_i, len: chunk.len() })
+        }
+        let counted = chunks.iter().map(Vec::len).sum();
+        if counted != elements_count {
+            return Err(ChunkListError::ElementsCountMismatch { expected: elements_count, counted })
+        }
+        let mut list = ChunkList::new();
+        list.chunks = chunks.into_iter()
+            .filter(|chunk| !chunk.is_empty())
+            .map(|chunk| Chunk { elements: VecDeque::from(chunk), chunk_size: Const })
+            .collect();
+        list.elements_count = elements_count;
+        Ok(list)
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Splits the vector into chunks from the back, so only the elements after the first N move.
+    fn adopt(chunk_size: C, mut elements: Vec<T>) -> Vec<Chunk<T, C>> {
+        let n = chunk_size.get();
+        let mut chunks = Vec::new();
+        while elements.len() > n {
+            let at = (elements.len() - 1) / n * n;
+            chunks.push(Chunk { elements: VecDeque::from(elements.split_off(at)), chunk_size });
+        }
+        if !elements.is_empty() {
+            chunks.push(Chunk { elements: VecDeque::from(elements), chunk_size });
+        }
+        chunks.reverse();
+        chunks
+    }
+
+    /// Flattens the list into a vector by bulk-moving chunk contents.
+    pub fn into_vec(self) -> Vec<T> {
+        let mut vec = Vec::with_capacity(self.elements_count);
+        for mut chunk in self.chunks {
+            vec.extend(chunk.elements.drain(..));
+        }
+        vec
+    }
+
+    /// Part of the chunk slots which hold elements, 1 for an empty list.
+    pub fn fill_factor(&self) -> f64 {
+        if self.chunks.is_empty() {
+            return 1.0
+        }
+        self.elements_count as f64 / (self.chunks.len() * self.chunk_size.get()) as f64
+    }
+
+    /// Occupancy of the chunks in the spine, None for an empty list.
+    pub fn occupancy_stats(&self) -> Option<OccupancyStats> {
+        let mut histogram = vec![0; self.chunk_size.get() + 1];
+        for chunk in &self.chunks {
+            histogram[chunk.len()] += 1;
+        }
+        Some(OccupancyStats {
+            min: self.chunks.iter().map(Chunk::len).min()?,
+            max: self.chunks.iter().map(Chunk::len).max()?,
+            average: self.elements_count as f64 / self.chunks.len() as f64,
+            histogram,
+        })
+    }
+
+    /// Bytes allocated by the list itself, heap memory owned by the elements is not included.
+    pub fn heap_size_bytes(&self) -> usize {
+        self.heap_size().total()
+    }
+
+    /// Breakdown of `heap_size_bytes`.
+    pub fn heap_size(&self) -> HeapSize {
+        let element_size = mem::size_of::<T>();
+        let chunk_size = mem::size_of::<Chunk<T, C>>();
+        let buffers_capacity: usize = self.chunks.iter().chain(&self.spare_chunks)
+            .map(|chunk| chunk.elements.capacity())
+            .sum();
+        HeapSize {
+            spine: (self.chunks.capacity() + self.spare_chunks.capacity()) * chunk_size,
+            chunk_buffers: buffers_capacity * element_size,
+            wasted: (buffers_capacity - self.elements_count) * element_size,
+        }
+    }
+
+    /// Validates the internal bookkeeping in O(chunks) and reports every violation found.
+    /// Available in tests and with the `check-invariants` feature.
+    #[cfg(any(test, feature = "check-invariants"))]
+    pub fn check_invariants(&self) -> Result<(), Vec<InvariantViolation>> {
+        let mut violations = Vec::new();
+        let mut counted = 0;
+        for (chunk_i, chunk) in self.chunks.iter().enumerate() {
+            if chunk.is_empty() && chunk_i > 0 && chunk_i + 1 < self.chunks.len() {
+                violations.push(InvariantViolation::EmptyChunk { chunk_i });
+            }
+            if chunk.len() > self.chunk_size.get() {
+                violations.push(InvariantViolation::ChunkOverflow { chunk_i, len: chunk.len() });
+            }
+            if !chunk.elements.as_slices().1.is_empty() {
+                violations.push(InvariantViolation::NonContiguousChunk { chunk_i });
+            }
+            counted += chunk.len();
+            if let Some(index) = self.chunk_index.as_ref().filter(|index| index.valid) {
+                let indexed = match chunk_i < index.tree.len() {
+                    true => index.prefix_len(chunk_i + 1),
+                    false => 0,
+                };
+                if indexed != counted {
+                    violations.push(InvariantViolation::ChunkIndex { chunk_i, indexed, counted });
+                }
+            }
+        }
+        if counted != self.elements_count {
+            violations.push(InvariantViolation::ElementsCount { stored: self.elements_count, counted });
+        }
+        for (spare_i, chunk) in self.spare_chunks.iter().enumerate() {
+            if !chunk.is_empty() {
+                violations.push(InvariantViolation::NonEmptySpareChunk { spare_i, len: chunk.len() });
+            }
+        }
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+
+    /// Repacks elements into the minimum number of chunks in one pass, every chunk
+    /// except the last one ends up full. Returns how many chunks were freed.
+    pub fn defragment(&mut self) -> usize {
+        let chunks_count = self.chunks.len();
+        self.pack();
+        chunks_count - self.chunks.len()
+    }
+
+    /// Moves all elements into a single chunk and returns them as a slice,
+    /// returns None if there are more than N elements.
+    pub fn make_contiguous(&mut self) -> Option<&mut [T]> {
+        if self.elements_count > self.chunk_size.get() {
+            return None
+        }
+        self.pack();
+        match self.chunks.front_mut() {
+            Some(chunk) => Some(chunk.as_mut_slice()),
+            None => Some(&mut []),
+        }
+    }
+
+    /// Consumes the list yielding the contents of every chunk without moving elements.
+    pub fn into_chunks(self) -> IntoChunks<T, C> {
+        IntoChunks(self.chunks.into_iter())
+    }
+}
+
+impl<'a, T, C: Capacity> Iterator for Chunks<'a, T, C> {
+    type Item = &'a [T];
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Chunk::as_slice)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, C: Capacity> DoubleEndedIterator for Chunks<'a, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(Chunk::as_slice)
+    }
+}
+
+impl<'a, T, C: Capacity> ExactSizeIterator for Chunks<'a, T, C> {}
+
+impl<'a, T, C: Capacity> FusedIterator for Chunks<'a, T, C> {}
+
+impl<'a, T, C: Capacity> Iterator for ChunksMut<'a, T, C> {
+    type Item = &'a mut [T];
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Chunk::as_mut_slice)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a, T, C: Capacity> DoubleEndedIterator for ChunksMut<'a, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(Chunk::as_mut_slice)
+    }
+}
+
+impl<'a, T, C: Capacity> ExactSizeIterator for ChunksMut<'a, T, C> {}
+
+impl<'a, T, C: Capacity> FusedIterator for ChunksMut<'a, T, C> {}
+
+impl<T, C: Capacity> Iterator for IntoChunks<T, C> {
+    type Item = Vec<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Chunk::into_vec)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<T, C: Capacity> DoubleEndedIterator for IntoChunks<T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(Chunk::into_vec)
+    }
+}
+
+impl<T, C: Capacity> ExactSizeIterator for IntoChunks<T, C> {}
+
+impl<T, C: Capacity> FusedIterator for IntoChunks<T, C> {}
+
+// --------------------
+// DRAIN
+// --------------------
+/// Removed elements are detached from the list when the iterator is created,
+/// the ones left unconsumed are dropped together with the iterator.
+pub struct Drain<'a, T, C> {
+    iter: IntoIter<T, C>,
+    _list: PhantomData<&'a mut ChunkListBase<T, C>>,
+}
+
+/// Converts range bounds to `start..end`, panicking if they don't fit into len.
+fn range_to_indices(range: impl RangeBounds<usize>, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if start > end {
+        panic!("range index starts at {} but ends at {}", start, end)
+    }
+    if end > len {
+        panic!("range end index {} out of range for length {}", end, len)
+    }
+    (start, end)
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Removes the elements in range and returns them as an iterator.
+    /// Whole chunks inside the range are moved out without touching their elements.
+    pub fn drain(&mut self, range: impl RangeBounds<usize>) -> Drain<'_, T, C> {
+        let (start, end) = range_to_indices(range, self.elements_count);
+        let mut drained = Self::with_chunk_size(self.chunk_size);
+        if let Some((mut chunk_i, element_i)) = self.locate(start) {
+            let mut count = end - start;
+            drained.elements_count = count;
+            self.elements_count -= count;
+            if element_i > 0 {
+                let chunk = &mut self.chunks[chunk_i];
+                let taken = count.min(chunk.len() - element_i);
+                drained.add_new_chunk_back().extend(chunk.elements.drain(element_i..element_i + taken));
+                count -= taken;
+                chunk_i += 1;
+            }
+            let mut whole_end = chunk_i;
+            while count > 0 && self.chunks[whole_end].len() <= count {
+                count -= self.chunks[whole_end].len();
+                whole_end += 1;
+            }
+            drained.chunks.extend(self.chunks.drain(chunk_i..whole_end));
+            if count > 0 {
+                drained.add_new_chunk_back().extend(self.chunks[chunk_i].elements.drain(..count));
+            }
+            self.rebalance();
+            self.update_chunk_index();
+        }
+        Drain { iter: drained.into_iter(), _list: PhantomData }
+    }
+
+    /// Replaces elements in range with `replace_with`, returns the removed elements.
+    /// Unlike `Vec::splice` the replacement happens right away: the range is cut out
+    /// by chunks and the new elements are filled into fresh chunks in its place.
+    pub fn splice<I: IntoIterator<Item = T>>(&mut self, range: impl RangeBounds<usize>, replace_with: I) -> Drain<'_, T, C> {
+        let (start, end) = range_to_indices(range, self.elements_count);
+        let removed = self.drain(start..end).iter;
+        let mut tail = self.split_off(start);
+        self.extend(replace_with);
+        self.append(&mut tail);
+        Drain { iter: removed, _list: PhantomData }
+    }
+}
+
+impl<'a, T, C: Capacity> Iterator for Drain<'a, T, C> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.iter.nth(n)
+    }
+}
+
+impl<'a, T, C: Capacity> DoubleEndedIterator for Drain<'a, T, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a, T, C: Capacity> ExactSizeIterator for Drain<'a, T, C> {}
+
+impl<'a, T, C: Capacity> FusedIterator for Drain<'a, T, C> {}
+
+// --------------------
+// EXTRACT IF
+// --------------------
+/// Elements not visited before the iterator is dropped stay in the list.
+pub struct ExtractIf<'a, T, C, F: FnMut(&mut T) -> bool> {
+    chunk_list: &'a mut ChunkListBase<T, C>,
+    chunk_i: usize,
+    element_i: usize,
+    pred: F,
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Lazily removes and yields the elements for which `pred` returns true.
+    pub fn extract_if<F: FnMut(&mut T) -> bool>(&mut self, pred: F) -> ExtractIf<'_, T, C, F> {
+        ExtractIf { chunk_list: self, chunk_i: 0, element_i: 0, pred }
+    }
+}
+
+impl<'a, T, C: Capacity, F: FnMut(&mut T) -> bool> Iterator for ExtractIf<'a, T, C, F> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let chunk = self.chunk_list.chunks.get_mut(self.chunk_i)?;
+            if self.element_i >= chunk.len() {
+                self.chunk_i += 1;
+                self.element_i = 0;
+                continue;
+            }
+            if !(self.pred)(&mut chunk.elements[self.element_i]) {
+                self.element_i += 1;
+                continue;
+            }
+            let value = chunk.elements.remove(self.element_i).unwrap();
+            if chunk.is_empty() {
+                let chunk = self.chunk_list.remove_chunk(self.chunk_i);
+                self.chunk_list.recycle_chunk(chunk);
+            }
+            self.chunk_list.chunk_index_add(self.chunk_i, -1);
+            self.chunk_list.elements_count -= 1;
+            return Some(value)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.chunk_list.elements_count))
+    }
+}
+
+// --------------------
+// CURSOR
+// --------------------
+/// Position of a cursor, `chunk_i == chunks_count()` stands for the "ghost"
+/// non-element between the back and the front of the list.
+#[derive(Clone, Copy)]
+struct Position {
+    chunk_i: usize,
+    element_i: usize,
+    index: usize,
+}
+
+impl Position {
+    fn ghost<T, C: Capacity>(list: &ChunkListBase<T, C>) -> Self {
+        Position { chunk_i: list.chunks.len(), element_i: 0, index: list.elements_count }
+    }
+
+    fn front<T, C: Capacity>(list: &ChunkListBase<T, C>) -> Self {
+        Position { chunk_i: 0, element_i: 0, index: 0 }.normalized(list)
+    }
+
+    fn back<T, C: Capacity>(list: &ChunkListBase<T, C>) -> Self {
+        match list.chunks.back() {
+            Some(back) => Position {
+                chunk_i: list.chunks.len() - 1,
+                element_i: back.len() - 1,
+                index: list.elements_count - 1,
+            },
+            None => Position::ghost(list),
+        }
+    }
+
+    fn at<T, C: Capacity>(list: &ChunkListBase<T, C>, index: usize) -> Self {
+        match list.locate(index) {
+            Some((chunk_i, element_i)) => Position { chunk_i, element_i, index },
+            None => Position::ghost(list),
+        }
+    }
+
+    /// Moves a position pointing one past the end of a chunk to the start of the next one.
+    fn normalized<T, C: Capacity>(mut self, list: &ChunkListBase<T, C>) -> Self {
+        if self.chunk_i < list.chunks.len() && self.element_i == list.chunks[self.chunk_i].len() {
+            self.chunk_i += 1;
+            self.element_i = 0;
+        }
+        self
+    }
+
+    fn is_ghost<T, C: Capacity>(&self, list: &ChunkListBase<T, C>) -> bool {
+        self.chunk_i >= list.chunks.len()
+    }
+
+    fn move_next<T, C: Capacity>(&mut self, list: &ChunkListBase<T, C>) {
+        if self.is_ghost(list) {
+            *self = Position::front(list);
+            return
+        }
+        self.element_i += 1;
+        self.index += 1;
+        *self = self.normalized(list);
+    }
+
+    fn move_prev<T, C: Capacity>(&mut self, list: &ChunkListBase<T, C>) {
+        if self.is_ghost(list) {
+            *self = Position::back(list);
+        }
+        else if self.element_i > 0 {
+            self.element_i -= 1;
+            self.index -= 1;
+        }
+        else if self.chunk_i > 0 {
+            self.chunk_i -= 1;
+            self.element_i = list.chunks[self.chunk_i].len() - 1;
+            self.index -= 1;
+        }
+        else {
+            *self = Position::ghost(list);
+        }
+    }
+
+    /// Walks over chunks from the current position, so reaching nearby indices is O(1).
+    fn seek<T, C: Capacity>(&mut self, list: &ChunkListBase<T, C>, index: usize) {
+        if index >= list.elements_count {
+            *self = Position::ghost(list);
+            return
+        }
+        if self.is_ghost(list) {
+            *self = if index < list.elements_count / 2 { Position::front(list) } else { Position::back(list) };
+        }
+        let (mut chunk_i, mut chunk_start) = (self.chunk_i, self.index - self.element_i);
+        while index < chunk_start {
+            chunk_i -= 1;
+            chunk_start -= list.chunks[chunk_i].len();
+        }
+        while index >= chunk_start + list.chunks[chunk_i].len() {
+            chunk_start += list.chunks[chunk_i].len();
+            chunk_i += 1;
+        }
+        *self = Position { chunk_i, element_i: index - chunk_start, index };
+    }
+
+    fn get<'a, T, C: Capacity>(&self, list: &'a ChunkListBase<T, C>) -> Option<&'a T> {
+        list.chunks.get(self.chunk_i)?.elements.get(self.element_i)
+    }
+
+    fn get_mut<'a, T, C: Capacity>(&self, list: &'a mut ChunkListBase<T, C>) -> Option<&'a mut T> {
+        list.chunks.get_mut(self.chunk_i)?.elements.get_mut(self.element_i)
+    }
+}
+
+/// Read-only cursor over a list, works like `std::collections::linked_list::Cursor`.
+/// Moving the cursor and reading neighbours is O(1).
+pub struct Cursor<'a, T, C> {
+    list: &'a ChunkListBase<T, C>,
+    position: Position,
+}
+
+impl<'a, T, C: Capacity> Clone for Cursor<'a, T, C> {
+    fn clone(&self) -> Self {
+        Cursor { list: self.list, position: self.position }
+    }
+}
+
+impl<'a, T, C: Capacity> Cursor<'a, T, C> {
+    /// Returns None if the cursor points at the ghost non-element.
+    pub fn index(&self) -> Option<usize> {
+        if self.position.is_ghost(self.list) {
+            return None
+        }
+        Some(self.position.index)
+    }
+
+    pub fn move_next(&mut self) {
+        self.position.move_next(self.list)
+    }
+
+    pub fn move_prev(&mut self) {
+        self.position.move_prev(self.list)
+    }
+
+    /// Moves the cursor to `index`, or to the ghost if it is out of bounds. Only chunks
+    /// between the current position and `index` are visited, which makes sequential
+    /// and nearby lookups O(1) instead of O(chunks) for `get`.
+    pub fn seek(&mut self, index: usize) {
+        self.position.seek(self.list, index)
+    }
+
+    pub fn current(&self) -> Option<&'a T> {
+        self.position.get(self.list)
+    }
+
+    pub fn peek_next(&self) -> Option<&'a T> {
+        let mut position = self.position;
+        position.move_next(self.list);
+        position.get(self.list)
+    }
+
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        let mut position = self.position;
+        position.move_prev(self.list);
+        position.get(self.list)
+    }
+
+    pub fn front(&self) -> Option<&'a T> {
+        self.list.front()
+    }
+
+    pub fn back(&self) -> Option<&'a T> {
+        self.list.back()
+    }
+}
+
+/// Cursor that can also edit the list, works like `std::collections::linked_list::CursorMut`.
+/// Insertions and removals only touch the chunk under the cursor.
+pub struct CursorMut<'a, T, C> {
+    list: &'a mut ChunkListBase<T, C>,
+    position: Position,
+}
+
+impl<'a, T, C: Capacity> CursorMut<'a, T, C> {
+    /// Returns None if the cursor points at the ghost non-element.
+    pub fn index(&self) -> Option<usize> {
+        if self.position.is_ghost(self.list) {
+            return None
+        }
+        Some(self.position.index)
+    }
+
+    pub fn move_next(&mut self) {
+        self.position.move_next(self.list)
+    }
+
+    pub fn move_prev(&mut self) {
+        self.position.move_prev(self.list)
+    }
+
+    /// Same as `Cursor::seek`.
+    pub fn seek(&mut self, index: usize) {
+        self.position.seek(self.list, index)
+    }
+
+    pub fn current(&mut self) -> Option<&mut T> {
+        self.position.get_mut(self.list)
+    }
+
+    pub fn peek_next(&mut self) -> Option<&mut T> {
+        let mut position = self.position;
+        position.move_next(self.list);
+        position.get_mut(self.list)
+    }
+
+    pub fn peek_prev(&mut self) -> Option<&mut T> {
+        let mut position = self.position;
+        position.move_prev(self.list);
+        position.get_mut(self.list)
+    }
+
+    pub fn as_cursor(&self) -> Cursor<'_, T, C> {
+        Cursor { list: self.list, position: self.position }
+    }
+
+    /// Inserts after the current element, or at the front if the cursor is at the ghost.
+    pub fn insert_after(&mut self, value: T) {
+        if self.position.is_ghost(self.list) {
+            self.list.insert_at(0, 0, value);
+            self.position = Position::ghost(self.list);
+            return
+        }
+        let (chunk_i, element_i) = self.list.insert_at(self.position.chunk_i, self.position.element_i + 1, value);
+        if element_i > 0 {
+            self.position.chunk_i = chunk_i;
+            self.position.element_i = element_i - 1;
+        }
+        else {
+            self.position.chunk_i = chunk_i - 1;
+            self.position.element_i = self.list.chunks[chunk_i - 1].len() - 1;
+        }
+    }
+
+    /// Inserts before the current element, or at the back if the cursor is at the ghost.
+    pub fn insert_before(&mut self, value: T) {
+        if self.position.is_ghost(self.list) {
+            self.list.push_back(value);
+            self.position = Position::ghost(self.list);
+            return
+        }
+        let (chunk_i, element_i) = self.list.insert_at(self.position.chunk_i, self.position.element_i, value);
+        self.position = Position { chunk_i, element_i: element_i + 1, index: self.position.index + 1 }
+            .normalized(self.list);
+    }
+
+    /// Removes the current element and moves the cursor to the next one.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.position.is_ghost(self.list) {
+            return None
+        }
+        let chunk = &mut self.list.chunks[self.position.chunk_i];
+        let value = chunk.elements.remove(self.position.element_i).unwrap();
+        self.list.chunk_index_add(self.position.chunk_i, -1);
+        if self.list.chunks[self.position.chunk_i].is_empty() {
+            let chunk = self.list.remove_chunk(self.position.chunk_i);
+            self.list.recycle_chunk(chunk);
+            self.position.element_i = 0;
+        }
+        self.list.elements_count -= 1;
+        self.position = self.position.normalized(self.list);
+        Some(value)
+    }
+
+    /// Moves all elements of `other` after the current element, or to the front if
+    /// the cursor is at the ghost.
+    ///
+    /// Panics if the lists have different chunk sizes.
+    pub fn splice_after(&mut self, mut other: ChunkListBase<T, C>) {
+        let ghost = self.position.is_ghost(self.list);
+        let at = if ghost { 0 } else { self.position.index + 1 };
+        let mut tail = self.list.split_off(at);
+        self.list.append(&mut other);
+        self.list.append(&mut tail);
+        self.position = if ghost {
+            Position::ghost(self.list)
+        } else {
+            Position::at(self.list, self.position.index)
+        };
+    }
+
+    /// Moves all elements of `other` before the current element, or to the back if
+    /// the cursor is at the ghost.
+    ///
+    /// Panics if the lists have different chunk sizes.
+    pub fn splice_before(&mut self, mut other: ChunkListBase<T, C>) {
+        let ghost = self.position.is_ghost(self.list);
+        let at = if ghost { self.list.elements_count } else { self.position.index };
+        let inserted = other.elements_count;
+        let mut tail = self.list.split_off(at);
+        self.list.append(&mut other);
+        self.list.append(&mut tail);
+        self.position = if ghost {
+            Position::ghost(self.list)
+        } else {
+            Position::at(self.list, self.position.index + inserted)
+        };
+    }
+
+    /// Inserts before the current element like `insert_before`, but keeps the chunk in front
+    /// of the cursor as a gap buffer: the first insert splits the current chunk at the cursor,
+    /// the following ones push to the tail of that chunk, and once it is full a new empty
+    /// chunk becomes the gap. Repeated inserts at the cursor are O(1) amortized.
+    pub fn gap_insert_before(&mut self, value: T) {
+        let list = &mut *self.list;
+        let Position { mut chunk_i, element_i, index } = self.position;
+        if element_i > 0 {
+            let tail = list.chunks[chunk_i].elements.split_off(element_i);
+            let mut chunk = list.new_chunk();
+            chunk.extend(tail);
+            list.chunks.insert(chunk_i + 1, chunk);
+            list.invalidate_chunk_index();
+            chunk_i += 1;
+        }
+        if chunk_i == 0 || list.chunks[chunk_i - 1].is_full() {
+            let chunk = list.new_chunk();
+            list.chunks.insert(chunk_i, chunk);
+            list.invalidate_chunk_index();
+            chunk_i += 1;
+        }
+        list.chunks[chunk_i - 1].push_back(value);
+        list.chunk_index_add(chunk_i - 1, 1);
+        list.elements_count += 1;
+        self.position = Position { chunk_i, element_i: 0, index: index + 1 };
+    }
+
+    /// Removes the element before the cursor, in O(1) if it is the tail of a chunk
+    /// as after `gap_insert_before`. Returns None at the front of the list.
+    pub fn gap_remove_before(&mut self) -> Option<T> {
+        let list = &mut *self.list;
+        let Position { mut chunk_i, mut element_i, index } = self.position;
+        if index == 0 {
+            return None
+        }
+        let (removed_from, value) = match element_i {
+            0 => (chunk_i - 1, list.chunks[chunk_i - 1].pop_back()),
+            _ => {
+                element_i -= 1;
+                (chunk_i, list.chunks[chunk_i].elements.remove(element_i))
+            }
+        };
+        list.chunk_index_add(removed_from, -1);
+        // Only the chunk before the cursor can run empty, the current one still holds the cursor.
+        if list.chunks[removed_from].is_empty() {
+            let chunk = list.remove_chunk(removed_from);
+            list.recycle_chunk(chunk);
+            chunk_i -= 1;
+        }
+        list.elements_count -= 1;
+        self.position = Position { chunk_i, element_i, index: index - 1 };
+        value
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn cursor_front(&self) -> Cursor<'_, T, C> {
+        Cursor { list: self, position: Position::front(self) }
+    }
+
+    pub fn cursor_back(&self) -> Cursor<'_, T, C> {
+        Cursor { list: self, position: Position::back(self) }
+    }
+
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T, C> {
+        let position = Position::front(self);
+        CursorMut { list: self, position }
+    }
+
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T, C> {
+        let position = Position::back(self);
+        CursorMut { list: self, position }
+    }
+}
+
+// --------------------
+// HANDLES
+// --------------------
+/// Handle to an element of `HandleChunkList`, stays valid until the element is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ElementId {
+    slot: u32,
+    generation: u32,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+/// Chunk list whose `push_*` methods return handles to the pushed elements.
+/// Values live in a generational slot arena and the list keeps their order,
+/// so handles survive any other insertions and removals.
+/// Elements removed by handle are left in the order list as stale ids that are
+/// skipped on iteration and cleaned up once there are too many of them.
+pub struct HandleChunkList<T, const N: usize> {
+    order: ChunkList<ElementId, N>,
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<u32>,
+    len: usize,
+}
+
+impl<T, const N: usize> Default for HandleChunkList<T, N> {
+    fn default() -> Self {
+        HandleChunkList::new()
+    }
+}
+
+impl<T, const N: usize> HandleChunkList<T, N> {
+    pub fn new() -> Self {
+        HandleChunkList {
+            order: ChunkList::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn occupy_slot(&mut self, value: T) -> ElementId {
+        self.len += 1;
+        match self.free_slots.pop() {
+            Some(slot) => {
+                let free = &mut self.slots[slot as usize];
+                free.value = Some(value);
+                ElementId { slot, generation: free.generation }
+            }
+            None => {
+                let slot = u32::try_from(self.slots.len()).expect("too many elements for u32 handles");
+                self.slots.push(Slot { generation: 0, value: Some(value) });
+                ElementId { slot, generation: 0 }
+            }
+        }
+    }
+
+    /// Returns None if the handle is stale.
+    fn vacate_slot(&mut self, id: ElementId) -> Option<T> {
+        let slot = self.slots.get_mut(id.slot as usize)?;
+        if slot.generation != id.generation {
+            return None
+        }
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.slot);
+        self.len -= 1;
+        Some(value)
+    }
+
+    pub fn push_back(&mut self, value: T) -> ElementId {
+        let id = self.occupy_slot(value);
+        self.order.push_back(id);
+        id
+    }
+
+    pub fn push_front(&mut self, value: T) -> ElementId {
+        let id = self.occupy_slot(value);
+        self.order.push_front(id);
+        id
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        loop {
+            let id = self.order.pop_back()?;
+            if let Some(value) = self.vacate_slot(id) {
+                return Some(value)
+            }
+        }
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        loop {
+            let id = self.order.pop_front()?;
+            if let Some(value) = self.vacate_slot(id) {
+                return Some(value)
+            }
+        }
+    }
+
+    pub fn contains(&self, id: ElementId) -> bool {
+        self.get_by_handle(id).is_some()
+    }
+
+    pub fn get_by_handle(&self, id: ElementId) -> Option<&T> {
+        let slot = self.slots.get(id.slot as usize)?;
+        if slot.generation != id.generation {
+            return None
+        }
+        slot.value.as_ref()
+    }
+
+    pub fn get_by_handle_mut(&mut self, id: ElementId) -> Option<&mut T> {
+        let slot = self.slots.get_mut(id.slot as usize)?;
+        if slot.generation != id.generation {
+            return None
+        }
+        slot.value.as_mut()
+    }
+
+    /// Returns None if the handle is stale.
+    pub fn remove_by_handle(&mut self, id: ElementId) -> Option<T> {
+        let value = self.vacate_slot(id)?;
+        let stale = self.order.elements_count() - self.len;
+        if stale > self.len + N {
+            let slots = &self.slots;
+            self.order.retain(|id| slots[id.slot as usize].generation == id.generation);
+        }
+        Some(value)
+    }
+
+    /// Slots are kept and their generations bumped, so that handles taken before stay stale.
+    pub fn clear(&mut self) {
+        self.order.clear();
+        for (slot_i, slot) in self.slots.iter_mut().enumerate() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free_slots.push(slot_i as u32);
+            }
+        }
+        self.len = 0;
+    }
+
+    /// Iterates over handles and elements in list order.
+    pub fn iter(&self) -> HandleIter<'_, T, N> {
+        HandleIter { ids: self.order.iter(), slots: &self.slots }
+    }
+}
+
+pub struct HandleIter<'a, T, const N: usize> {
+    ids: Iter<'a, ElementId, Const<N>>,
+    slots: &'a [Slot<T>],
+}
+
+impl<'a, T, const N: usize> Iterator for HandleIter<'a, T, N> {
+    type Item = (ElementId, &'a T);
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let id = *self.ids.next()?;
+            let slot = &self.slots[id.slot as usize];
+            if slot.generation == id.generation {
+                return Some((id, slot.value.as_ref().unwrap()))
+            }
+        }
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for HandleIter<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let id = *self.ids.next_back()?;
+            let slot = &self.slots[id.slot as usize];
+            if slot.generation == id.generation {
+                return Some((id, slot.value.as_ref().unwrap()))
+            }
+        }
+    }
+}
+
+impl<'a, T, const N: usize> FusedIterator for HandleIter<'a, T, N> {}
+
+// --------------------
+// SORT
+// --------------------
+/// Merges two sorted iterators, taking from `a` on ties.
+struct MergeBy<A: Iterator, B: Iterator<Item = A::Item>, F> {
+    a: Peekable<A>,
+    b: Peekable<B>,
+    compare: F,
+}
+
+impl<A, B, F> MergeBy<A, B, F>
+where A: Iterator, B: Iterator<Item = A::Item>, F: FnMut(&A::Item, &A::Item) -> Ordering {
+    fn new(a: A, b: B, compare: F) -> Self {
+        MergeBy { a: a.peekable(), b: b.peekable(), compare }
+    }
+}
+
+impl<A, B, F> Iterator for MergeBy<A, B, F>
+where A: Iterator, B: Iterator<Item = A::Item>, F: FnMut(&A::Item, &A::Item) -> Ordering {
+    type Item = A::Item;
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.a.peek(), self.b.peek()) {
+            (Some(x), Some(y)) => {
+                if (self.compare)(y, x) == Ordering::Less {
+                    self.b.next()
+                } else {
+                    self.a.next()
+                }
+            }
+            (Some(_), None) => self.a.next(),
+            (None, _) => self.b.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (a_lower, a_upper) = self.a.size_hint();
+        let (b_lower, b_upper) = self.b.size_hint();
+        let upper = a_upper.zip(b_upper).and_then(|(a, b)| a.checked_add(b));
+        (a_lower.saturating_add(b_lower), upper)
+    }
+}
+
+/// Recomputes the element count and the chunk index if a comparator panics in the middle
+/// of a sort, elements which were moved out of the chunks are dropped then.
+struct RecountOnUnwind<'a, T, C: Capacity>(&'a mut ChunkListBase<T, C>);
+
+impl<T, C: Capacity> Deref for RecountOnUnwind<'_, T, C> {
+    type Target = ChunkListBase<T, C>;
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<T, C: Capacity> DerefMut for RecountOnUnwind<'_, T, C> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.0
+    }
+}
+
+impl<T, C: Capacity> Drop for RecountOnUnwind<'_, T, C> {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.0.elements_count = self.0.chunks.iter().map(Chunk::len).sum();
+            self.0.update_chunk_index();
+        }
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Stable sort.
+    pub fn sort(&mut self)
+    where T: Ord {
+        self.sort_by(T::cmp)
+    }
+
+    /// Stable sort by a key extraction function.
+    pub fn sort_by_key<K: Ord>(&mut self, mut f: impl FnMut(&T) -> K) {
+        self.sort_by(|a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Stable sort with a comparator function.
+    /// Every chunk is sorted separately, then sorted chunks are merged pairwise
+    /// which makes a k-way merge in log(k) passes.
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+        let mut list = RecountOnUnwind(self);
+        if list.chunks.len() <= 1 {
+            if let Some(chunk) = list.chunks.front_mut() {
+                chunk.as_mut_slice().sort_by(&mut compare);
+            }
+            return
+        }
+        let mut runs: Vec<Vec<T>> = mem::take(&mut list.chunks)
+            .into_iter()
+            .map(|mut chunk| {
+                chunk.as_mut_slice().sort_by(&mut compare);
+                chunk.into_vec()
+            })
+            .collect();
+        while runs.len() > 1 {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(2));
+            let mut runs_iter = runs.into_iter();
+            while let Some(a) = runs_iter.next() {
+                match runs_iter.next() {
+                    Some(b) => merged.push(merge_sorted_vecs(a, b, &mut compare)),
+                    None => merged.push(a),
+                }
+            }
+            runs = merged;
+        }
+        let elements_count = list.elements_count;
+        list.elements_count = 0;
+        list.update_chunk_index();
+        let mut sorted = runs.pop().unwrap();
+        list.extend(sorted.drain(..));
+        release_vec(sorted);
+        debug_assert_eq!(list.elements_count, elements_count);
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Moves elements toward the front so that every chunk except the last one is full.
+    fn pack(&mut self) {
+        let mut write_i = 0;
+        for read_i in 1..self.chunks.len() {
+            while write_i < read_i {
+                let mut range = self.chunks.range_mut(write_i..=read_i);
+                let (write, read) = (range.next().unwrap(), range.next_back().unwrap());
+                let taken = (self.chunk_size.get() - write.len()).min(read.len());
+                write.extend(read.elements.drain(..taken));
+                if write.is_full() {
+                    write_i += 1;
+                }
+                if read.is_empty() {
+                    break;
+                }
+            }
+        }
+        self.remove_empty_chunks();
+        self.update_chunk_index();
+    }
+
+    /// Unstable sort.
+    pub fn sort_unstable(&mut self)
+    where T: Ord {
+        self.sort_unstable_by(T::cmp)
+    }
+
+    /// Unstable sort by a key extraction function.
+    pub fn sort_unstable_by_key<K: Ord>(&mut self, mut f: impl FnMut(&T) -> K) {
+        self.sort_unstable_by(|a, b| f(a).cmp(&f(b)))
+    }
+
+    /// Unstable sort with a comparator function, doesn't allocate.
+    /// The list is packed into full chunks so that positions map to chunks in O(1),
+    /// then it is quicksorted in place. Ranges that fit into one chunk are
+    /// handed over to `slice::sort_unstable_by`.
+    pub fn sort_unstable_by(&mut self, mut compare: impl FnMut(&T, &T) -> Ordering) {
+        let mut list = RecountOnUnwind(self);
+        list.pack();
+        let depth_limit = 2 * (usize::BITS - list.elements_count.leading_zeros());
+        let (chunk_size, elements_count) = (list.chunk_size.get(), list.elements_count);
+        Packed { chunks: &mut list.chunks, chunk_size }.quicksort(0, elements_count, depth_limit, &mut compare);
+    }
+}
+
+/// Chunks where all but the last one are full, so the i-th element is at (i / N, i % N).
+struct Packed<'a, T, C> {
+    chunks: &'a mut VecDeque<Chunk<T, C>>,
+    chunk_size: usize,
+}
+
+impl<'a, T, C: Capacity> Packed<'a, T, C> {
+    fn get(&self, i: usize) -> &T {
+        &self.chunks[i / self.chunk_size].elements[i % self.chunk_size]
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        let (chunk_i, chunk_j) = (i / self.chunk_size, j / self.chunk_size);
+        if chunk_i == chunk_j {
+            self.chunks[chunk_i].elements.swap(i % self.chunk_size, j % self.chunk_size);
+            return
+        }
+        let (i, j) = if i < j { (i, j) } else { (j, i) };
+        let mut range = self.chunks.range_mut(i / self.chunk_size..=j / self.chunk_size);
+        let (first, last) = (range.next().unwrap(), range.next_back().unwrap());
+        mem::swap(&mut first.elements[i % self.chunk_size], &mut last.elements[j % self.chunk_size]);
+    }
+
+    fn less(&self, i: usize, j: usize, compare: &mut impl FnMut(&T, &T) -> Ordering) -> bool {
+        compare(self.get(i), self.get(j)) == Ordering::Less
+    }
+
+    /// Sorts `lo..hi`.
+    fn quicksort(&mut self, mut lo: usize, mut hi: usize, mut depth_limit: u32, compare: &mut impl FnMut(&T, &T) -> Ordering) {
+        while hi - lo > 1 {
+            if lo / self.chunk_size == (hi - 1) / self.chunk_size {
+                self.chunks[lo / self.chunk_size].as_mut_slice()[lo % self.chunk_size..=(hi - 1) % self.chunk_size].sort_unstable_by(&mut *compare);
+                return
+            }
+            if depth_limit == 0 {
+                self.heapsort(lo, hi, compare);
+                return
+            }
+            depth_limit -= 1;
+
+            // Median of three goes to lo and becomes the pivot.
+            let (a, b, c) = (lo, lo + (hi - lo) / 2, hi - 1);
+            if self.less(b, a, compare) {
+                self.swap(a, b);
+            }
+            if self.less(c, b, compare) {
+                self.swap(b, c);
+                if self.less(b, a, compare) {
+                    self.swap(a, b);
+                }
+            }
+            self.swap(lo, b);
+
+            let (mut i, mut j) = (lo + 1, hi - 1);
+            loop {
+                while i <= j && self.less(i, lo, compare) {
+                    i += 1;
+                }
+                while i <= j && self.less(lo, j, compare) {
+                    j -= 1;
+                }
+                if i >= j {
+                    break;
+                }
+                self.swap(i, j);
+                i += 1;
+                j -= 1;
+            }
+            self.swap(lo, j);
+
+            // Recurse into the smaller part to bound the stack depth.
+            if j - lo < hi - j - 1 {
+                self.quicksort(lo, j, depth_limit, compare);
+                lo = j + 1;
+            }
+            else {
+                self.quicksort(j + 1, hi, depth_limit, compare);
+                hi = j;
+            }
+        }
+    }
+
+    /// Sorts `lo..hi`, used when quicksort goes too deep.
+    fn heapsort(&mut self, lo: usize, hi: usize, compare: &mut impl FnMut(&T, &T) -> Ordering) {
+        let len = hi - lo;
+        for root in (0..len / 2).rev() {
+            self.sift_down(lo, root, len, compare);
+        }
+        for end in (1..len).rev() {
+            self.swap(lo, lo + end);
+            self.sift_down(lo, 0, end, compare);
+        }
+    }
+
+    /// Sifts down `root` in the max-heap stored at `lo..lo + end`.
+    fn sift_down(&mut self, lo: usize, mut root: usize, end: usize, compare: &mut impl FnMut(&T, &T) -> Ordering) {
+        loop {
+            let mut child = 2 * root + 1;
+            if child >= end {
+                break;
+            }
+            if child + 1 < end && self.less(lo + child, lo + child + 1, compare) {
+                child += 1;
+            }
+            if !self.less(lo + root, lo + child, compare) {
+                break;
+            }
+            self.swap(lo + root, lo + child);
+            root = child;
+        }
+    }
+}
+
+/// Iterator over two merged sorted lists, see `ChunkList::merge_iter`.
+pub struct Merge<T, C: Capacity, F> {
+    inner: MergeBy<IntoIter<T, C>, IntoIter<T, C>, F>,
+}
+
+impl<T, C: Capacity, F: FnMut(&T, &T) -> Ordering> Iterator for Merge<T, C, F> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T, C: Capacity, F: FnMut(&T, &T) -> Ordering> ExactSizeIterator for Merge<T, C, F> {}
+
+impl<T, C: Capacity, F: FnMut(&T, &T) -> Ordering> FusedIterator for Merge<T, C, F> {}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    /// Merges two sorted lists into a sorted list with full chunks of the chunk size of `self`.
+    /// Equal elements of `self` go before the ones of `other`.
+    pub fn merge(self, other: Self) -> Self
+    where T: Ord {
+        self.merge_by(other, T::cmp)
+    }
+
+    pub fn merge_by(self, other: Self, compare: impl FnMut(&T, &T) -> Ordering) -> Self {
+        let mut merged = Self::with_chunk_size(self.chunk_size);
+        merged.extend(self.merge_iter_by(other, compare));
+        merged
+    }
+
+    /// Lazy version of `merge`.
+    pub fn merge_iter(self, other: Self) -> Merge<T, C, fn(&T, &T) -> Ordering>
+    where T: Ord {
+        self.merge_iter_by(other, T::cmp)
+    }
+
+    pub fn merge_iter_by<F: FnMut(&T, &T) -> Ordering>(self, other: Self, compare: F) -> Merge<T, C, F> {
+        Merge { inner: MergeBy::new(self.into_iter(), other.into_iter(), compare) }
+    }
+}
+
+fn merge_sorted_vecs<T>(mut a: Vec<T>, mut b: Vec<T>, compare: &mut impl FnMut(&T, &T) -> Ordering) -> Vec<T> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    merged.extend(MergeBy::new(a.drain(..), b.drain(..), compare));
+    release_vec(a);
+    release_vec(b);
+    merged
+}
+
+// --------------------
+// BINARY SEARCH
+// --------------------
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    pub fn binary_search(&self, value: &T) -> Result<usize, usize>
+    where T: Ord {
+        self.binary_search_by(|element| element.cmp(value))
+    }
+
+    pub fn binary_search_by_key<K: Ord>(&self, key: &K, mut f: impl FnMut(&T) -> K) -> Result<usize, usize> {
+        self.binary_search_by(|element| f(element).cmp(key))
+    }
+
+    /// Searches the sorted list like `slice::binary_search_by`.
+    /// Chunks are bisected by their last elements first, then the found chunk is bisected.
+    pub fn binary_search_by(&self, mut f: impl FnMut(&T) -> Ordering) -> Result<usize, usize> {
+        let chunk_i = self.chunk_partition_point(|last| f(last) == Ordering::Less);
+        let offset: usize = self.chunks.range(..chunk_i).map(Chunk::len).sum();
+        match self.chunks.get(chunk_i) {
+            Some(chunk) => chunk.as_slice().binary_search_by(f)
+                .map(|i| offset + i)
+                .map_err(|i| offset + i),
+            None => Err(offset),
+        }
+    }
+
+    /// Returns the index of the first element for which `pred` is false,
+    /// the list must be partitioned by `pred`.
+    pub fn partition_point(&self, mut pred: impl FnMut(&T) -> bool) -> usize {
+        let chunk_i = self.chunk_partition_point(&mut pred);
+        let offset: usize = self.chunks.range(..chunk_i).map(Chunk::len).sum();
+        match self.chunks.get(chunk_i) {
+            Some(chunk) => offset + chunk.as_slice().partition_point(pred),
+            None => offset,
+        }
+    }
+
+    /// Index of the first chunk whose last element fails `pred`. Empty chunks pass it, the ones
+    /// at the back are skipped so that they don't break the partitioning.
+    fn chunk_partition_point(&self, mut pred: impl FnMut(&T) -> bool) -> usize {
+        let trailing_empty = self.chunks.iter().rev().take_while(|chunk| chunk.is_empty()).count();
+        let (mut lo, mut hi) = (0, self.chunks.len() - trailing_empty);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.chunks[mid].as_slice().last() {
+                Some(last) if !pred(last) => hi = mid,
+                _ => lo = mid + 1,
+            }
+        }
+        lo
+    }
+
+    /// Index of the first element not less than `value` in the sorted list.
+    pub fn lower_bound(&self, value: &T) -> usize
+    where T: Ord {
+        self.partition_point(|element| element < value)
+    }
+
+    /// Index of the first element greater than `value` in the sorted list.
+    pub fn upper_bound(&self, value: &T) -> usize
+    where T: Ord {
+        self.partition_point(|element| element <= value)
+    }
+
+    /// Inserts the value into the sorted list after all equal elements,
+    /// returns the position where it was inserted.
+    pub fn insert_sorted(&mut self, value: T) -> usize
+    where T: Ord {
+        self.insert_sorted_by(value, T::cmp)
+    }
+
+    pub fn insert_sorted_by_key<K: Ord>(&mut self, value: T, mut f: impl FnMut(&T) -> K) -> usize {
+        self.insert_sorted_by(value, |a, b| f(a).cmp(&f(b)))
+    }
+
+    pub fn insert_sorted_by(&mut self, value: T, mut compare: impl FnMut(&T, &T) -> Ordering) -> usize {
+        let i = self.partition_point(|element| compare(element, &value) != Ordering::Greater);
+        self.insert(i, value);
+        i
+    }
+
+    pub fn is_sorted(&self) -> bool
+    where T: PartialOrd {
+        self.is_sorted_by(|a, b| a <= b)
+    }
+
+    pub fn is_sorted_by_key<K: PartialOrd>(&self, mut f: impl FnMut(&T) -> K) -> bool {
+        self.is_sorted_by(|a, b| f(a) <= f(b))
+    }
+
+    /// Checks that `compare(a, b)` holds for every pair of neighbouring elements,
+    /// like `slice::is_sorted_by`.
+    pub fn is_sorted_by(&self, mut compare: impl FnMut(&T, &T) -> bool) -> bool {
+        let mut previous_last: Option<&T> = None;
+        for chunk in &self.chunks {
+            let slice = chunk.as_slice();
+            if let (Some(last), Some(first)) = (previous_last, slice.first()) {
+                if !compare(last, first) {
+                    return false
+                }
+            }
+            if !slice.is_sorted_by(&mut compare) {
+                return false
+            }
+            previous_last = slice.last();
+        }
+        true
+    }
+}
+
+// --------------------
+// INDEX
+// --------------------
+impl<T, C: Capacity> Index<usize> for ChunkListBase<T, C> {
+    type Output = T;
+    fn index(&self, i: usize) -> &T {
+        match self.locate(i) {
+            Some((chunk_i, element_i)) => &self.chunks[chunk_i].as_slice()[element_i],
+            None => panic!("index out of bounds: the len is {} but the index is {}", self.elements_count, i),
+        }
+    }
+}
+
+impl<T, C: Capacity> IndexMut<usize> for ChunkListBase<T, C> {
+    fn index_mut(&mut self, i: usize) -> &mut T {
+        match self.locate_mut(i) {
+            Some((chunk_i, element_i)) => &mut self.chunks[chunk_i].as_mut_slice()[element_i],
+            None => panic!("index out of bounds: the len is {} but the index is {}", self.elements_count, i),
+        }
+    }
+}
+
+// --------------------
+// EQ
+// --------------------
+/// Compares two sequences of slices as if they were concatenated.
+fn slices_eq<'a, 'b, A, B>(
+    mut a: impl Iterator<Item = &'a [A]>,
+    mut b: impl Iterator<Item = &'b [B]>,
+) -> bool
+where A: PartialEq<B> + 'a, B: 'b {
+    let mut x: &[A] = &[];
+    let mut y: &[B] = &[];
+    loop {
+        while x.is_empty() {
+            match a.next() {
+                Some(slice) => x = slice,
+                None => break,
+            }
+        }
+        while y.is_empty() {
+            match b.next() {
+                Some(slice) => y = slice,
+                None => break,
+            }
+        }
+        if x.is_empty() || y.is_empty() {
+            return x.is_empty() && y.is_empty()
+        }
+        let n = x.len().min(y.len());
+        if x[..n] != y[..n] {
+            return false
+        }
+        x = &x[n..];
+        y = &y[n..];
+    }
+}
+
+impl<T, U, C: Capacity, D: Capacity> PartialEq<ChunkListBase<U, D>> for ChunkListBase<T, C>
+where T: PartialEq<U> {
+    fn eq(&self, other: &ChunkListBase<U, D>) -> bool {
+        self.elements_count == other.elements_count
+            && slices_eq(
+                self.chunks.iter().map(Chunk::as_slice),
+                other.chunks.iter().map(Chunk::as_slice),
+            )
+    }
+}
+
+impl<T: Eq, C: Capacity> Eq for ChunkListBase<T, C> {}
+
+impl<T, U, C: Capacity> PartialEq<&[U]> for ChunkListBase<T, C>
+where T: PartialEq<U> {
+    fn eq(&self, other: &&[U]) -> bool {
+        self.elements_count == other.len()
+            && slices_eq(self.chunks.iter().map(Chunk::as_slice), std::iter::once(*other))
+    }
+}
+
+impl<T, U, C: Capacity, const M: usize> PartialEq<[U; M]> for ChunkListBase<T, C>
+where T: PartialEq<U> {
+    fn eq(&self, other: &[U; M]) -> bool {
+        *self == other.as_slice()
+    }
+}
+
+impl<T, U, C: Capacity> PartialEq<Vec<U>> for ChunkListBase<T, C>
+where T: PartialEq<U> {
+    fn eq(&self, other: &Vec<U>) -> bool {
+        *self == other.as_slice()
+    }
+}
+
+// --------------------
+// ORD
+// --------------------
+/// Lexicographically compares two sequences of slices as if they were concatenated.
+fn slices_cmp<'a, T: 'a>(
+    mut a: impl Iterator<Item = &'a [T]>,
+    mut b: impl Iterator<Item = &'a [T]>,
+    mut cmp: impl FnMut(&[T], &[T]) -> Option<Ordering>,
+) -> Option<Ordering> {
+    let mut x: &[T] = &[];
+    let mut y: &[T] = &[];
+    loop {
+        while x.is_empty() {
+            match a.next() {
+                Some(slice) => x = slice,
+                None => break,
+            }
+        }
+        while y.is_empty() {
+            match b.next() {
+                Some(slice) => y = slice,
+                None => break,
+            }
+        }
+        if x.is_empty() || y.is_empty() {
+            return Some(x.len().cmp(&y.len()))
+        }
+        let n = x.len().min(y.len());
+        match cmp(&x[..n], &y[..n]) {
+            Some(Ordering::Equal) => {}
+            ordering => return ordering,
+        }
+        x = &x[n..];
+        y = &y[n..];
+    }
+}
+
+impl<T: PartialOrd, C: Capacity> PartialOrd for ChunkListBase<T, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        slices_cmp(
+            self.chunks.iter().map(Chunk::as_slice),
+            other.chunks.iter().map(Chunk::as_slice),
+            |x, y| x.partial_cmp(y),
+        )
+    }
+}
+
+impl<T: Ord, C: Capacity> Ord for ChunkListBase<T, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        slices_cmp(
+            self.chunks.iter().map(Chunk::as_slice),
+            other.chunks.iter().map(Chunk::as_slice),
+            |x, y| Some(x.cmp(y)),
+        ).unwrap()
+    }
+}
+
+// --------------------
+// HASH
+// --------------------
+impl<T: Hash, C: Capacity> Hash for ChunkListBase<T, C> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.elements_count);
+        // Hash::hash_slice can't be used per chunk: chunk lengths vary in
+        // otherwise equal lists and Hasher only guarantees equal results
+        // for the exact same sequence of calls.
+        self.iter().for_each(|value| value.hash(state));
+    }
+}
+
+// --------------------
+// FROM ITER / EXTEND
+// --------------------
+impl<T, const N: usize> FromIterator<T> for ChunkList<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut chunk_list = Self::new();
+        chunk_list.extend(iter);
+        chunk_list
+    }
+}
+
+impl<T, C: Capacity> Extend<T> for ChunkListBase<T, C> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let (chunks_count, back_len) = (self.chunks.len(), self.chunks.back().map_or(0, Chunk::len));
+        self.extend_chunks(iter);
+        self.chunk_index_grown_back(chunks_count, back_len);
+    }
+}
+
+impl<T, C: Capacity> ChunkListBase<T, C> {
+    fn extend_chunks<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        let chunk_size = self.chunk_size.get();
+        let spare = self.chunks.back().map_or(0, |chunk| chunk_size - chunk.len());
+        self.chunks.reserve(lower.saturating_sub(spare).div_ceil(chunk_size));
+        loop {
+            if self.chunks.back().is_none_or(|chunk| chunk.is_full()) {
+                // A new chunk is only added once there is an element for it.
+                let Some(value) = iter.next() else {
+                    return
+                };
+                self.push_new_chunk_back().push_back(value);
+                self.elements_count += 1;
+            }
+            let chunk = self.chunks.back_mut().unwrap();
+            let (len, spare) = (chunk.len(), chunk_size - chunk.len());
+            // Extending from Take lets the buffer copy in bulk when the iterator length is trusted.
+            chunk.extend(iter.by_ref().take(spare));
+            let added = chunk.len() - len;
+            self.elements_count += added;
+            if added < spare {
+                return
+            }
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a, C: Capacity> Extend<&'a T> for ChunkListBase<T, C> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied())
+    }
+}
+// --------------------
+// FROM
+// --------------------
+impl<T, const N: usize> From<Vec<T>> for ChunkList<T, N> {
+    fn from(vec: Vec<T>) -> Self {
+        vec.into_iter().collect()
+    }
+}
+
+impl<T: Clone, const N: usize> From<&[T]> for ChunkList<T, N> {
+    fn from(slice: &[T]) -> Self {
+        let mut chunk_list = Self::new();
+        chunk_list.extend_from_slice(slice);
+        chunk_list
+    }
+}
+
+impl<T, const N: usize, const M: usize> From<[T; M]> for ChunkList<T, N> {
+    fn from(array: [T; M]) -> Self {
+        array.into_iter().collect()
+    }
+}
+
+impl<T, const N: usize> From<VecDeque<T>> for ChunkList<T, N> {
+    fn from(deque: VecDeque<T>) -> Self {
+        deque.into_iter().collect()
+    }
+}
+
+impl<T, const N: usize> From<LinkedList<T>> for ChunkList<T, N> {
+    fn from(list: LinkedList<T>) -> Self {
+        list.into_iter().collect()
+    }
+}
+
+impl<T, C: Capacity> From<ChunkListBase<T, C>> for Vec<T> {
+    fn from(chunk_list: ChunkListBase<T, C>) -> Self {
+        chunk_list.into_vec()
+    }
+}
+
+impl<T, C: Capacity> From<ChunkListBase<T, C>> for VecDeque<T> {
+    fn from(chunk_list: ChunkListBase<T, C>) -> Self {
+        VecDeque::from(chunk_list.into_vec())
+    }
+}
+
+// --------------------
+// IO
+// --------------------
+impl<C: Capacity> ChunkListBase<u8, C> {
+    /// Reads until the end of input, see `extend_from_reader_limit`.
+    pub fn extend_from_reader(&mut self, reader: &mut impl io::Read) -> io::Result<usize> {
+        self.extend_from_reader_limit(reader, usize::MAX)
+    }
+
+    /// Reads at most `limit` bytes straight into the spare room of the back chunk and
+    /// then of new chunks, without an intermediate buffer. The room is zeroed before
+    /// every read since safe code can't hand out uninitialized memory.
+    /// Returns how many bytes were read, interrupted reads are retried and on other errors
+    /// the bytes read before stay in the list.
+    pub fn extend_from_reader_limit(&mut self, reader: &mut impl io::Read, limit: usize) -> io::Result<usize> {
+        let mut total = 0;
+        let result = loop {
+            if total == limit {
+                break Ok(total)
+            }
+            if self.chunks.back().is_none_or(Chunk::is_full) {
+                self.push_new_chunk_back();
+            }
+            let back = self.chunks.back_mut().unwrap();
+            let len = back.len();
+            let room = (self.chunk_size.get() - len).min(limit - total);
+            back.grow(room);
+            back.elements.resize(len + room, 0);
+            back.elements.make_contiguous();
+            let read = reader.read(&mut back.as_mut_slice()[len..]);
+            back.elements.truncate(len + *read.as_ref().unwrap_or(&0));
+            match read {
+                Ok(0) => break Ok(total),
+                Ok(read) => {
+                    total += read;
+                    self.elements_count += read;
+                }
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                Err(error) => break Err(error),
+            }
+        };
+        if self.chunks.back().is_some_and(Chunk::is_empty) {
+            let back = self.chunks.pop_back();
+            self.recycle_chunk(back);
+        }
+        self.update_chunk_index();
+        result
+    }
+}
+
+/// Bytes are appended to the tail of the back chunk and then to new chunks, writes never fail.
+impl<C: Capacity> io::Write for ChunkListBase<u8, C> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_copy_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.reserve(bufs.iter().map(|buf| buf.len()).sum());
+        let mut written = 0;
+        for buf in bufs {
+            self.extend_from_copy_slice(buf);
+            written += buf.len();
+        }
+        Ok(written)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.extend_from_copy_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Bytes are consumed from the front, emptied chunks go to the pool of spare chunks.
+impl<C: Capacity> io::Read for ChunkListBase<u8, C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        while read < buf.len() {
+            let Some(front) = self.chunks.iter().find(|chunk| !chunk.is_empty()) else {
+                break
+            };
+            let taken = front.len().min(buf.len() - read);
+            buf[read..read + taken].copy_from_slice(&front.as_slice()[..taken]);
+            read += taken;
+            io::BufRead::consume(self, taken);
+        }
+        Ok(read)
+    }
+}
+
+/// `fill_buf` returns the first non-empty chunk without copying, so one call sees at most N bytes.
+impl<C: Capacity> io::BufRead for ChunkListBase<u8, C> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.chunks.iter().find(|chunk| !chunk.is_empty()).map_or(&[], Chunk::as_slice))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.truncate_front(self.elements_count.saturating_sub(amt));
+    }
+}
+
+// --------------------
+// FIND
+// --------------------
+impl<C: Capacity> ChunkListBase<u8, C> {
+    /// Position of the first occurrence of `needle`, 0 for an empty needle.
+    /// Every chunk is searched with memmem, then the starts in its last `needle.len() - 1`
+    /// bytes are checked against the following chunks for matches across the boundary.
+    pub fn find(&self, needle: &[u8]) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(0)
+        }
+        let finder = memchr::memmem::Finder::new(needle);
+        let mut offset = 0;
+        for (chunk_i, chunk) in self.chunks.iter().enumerate() {
+            let slice = chunk.as_slice();
+            if let Some(i) = finder.find(slice) {
+                return Some(offset + i)
+            }
+            let spanning = slice.len().saturating_sub(needle.len() - 1)..slice.len();
+            if let Some(i) = spanning.into_iter().find(|&i| self.matches_at(chunk_i, i, needle)) {
+                return Some(offset + i)
+            }
+            offset += slice.len();
+        }
+        None
+    }
+
+    /// Position of the last occurrence of `needle`, `elements_count` for an empty needle.
+    pub fn rfind(&self, needle: &[u8]) -> Option<usize> {
+        if needle.is_empty() {
+            return Some(self.elements_count)
+        }
+        let finder = memchr::memmem::FinderRev::new(needle);
+        let mut offset = self.elements_count;
+        for (chunk_i, chunk) in self.chunks.iter().enumerate().rev() {
+            let slice = chunk.as_slice();
+            offset -= slice.len();
+            let spanning = slice.len().saturating_sub(needle.len() - 1)..slice.len();
+            if let Some(i) = spanning.into_iter().rev().find(|&i| self.matches_at(chunk_i, i, needle)) {
+                return Some(offset + i)
+            }
+            if let Some(i) = finder.rfind(slice) {
+                return Some(offset + i)
+            }
+        }
+        None
+    }
+
+    /// Whether `needle` starts at position `element_i` of the chunk, possibly spanning the next ones.
+    fn matches_at(&self, chunk_i: usize, element_i: usize, mut needle: &[u8]) -> bool {
+        let mut slices = self.chunks.range(chunk_i..).map(Chunk::as_slice);
+        let mut slice = &slices.next().unwrap()[element_i..];
+        loop {
+            let len = slice.len().min(needle.len());
+            if slice[..len] != needle[..len] {
+                return false
+            }
+            needle = &needle[len..];
+            if needle.is_empty() {
+                return true
+            }
+            match slices.next() {
+                Some(next) => slice = next,
+                None => return false,
+            }
+        }
+    }
+}
+
+// --------------------
+// BINARY
+// --------------------
+/// Fixed binary encoding of an element, used to move chunks out of memory.
+pub trait BinaryCodec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    /// Reads one element from the front of `input` and advances it.
+    fn decode(input: &mut &[u8]) -> io::Result<Self>;
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+macro_rules! impl_binary_codec {
+    ($($t:ty),*) => {$(
+        impl BinaryCodec for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes())
+            }
+
+            fn decode(input: &mut &[u8]) -> io::Result<Self> {
+                let (bytes, rest) = input.split_first_chunk().ok_or_else(|| invalid_data("truncated element"))?;
+                *input = rest;
+                Ok(<$t>::from_le_bytes(*bytes))
+            }
+        }
+    )*};
+}
+
+impl_binary_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+/// Encoded as `u64` so the encoding doesn't depend on the platform.
+impl BinaryCodec for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out)
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        usize::try_from(u64::decode(input)?).map_err(|_| invalid_data("usize out of range"))
+    }
+}
+
+/// Encoded as `i64` so the encoding doesn't depend on the platform.
+impl BinaryCodec for isize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as i64).encode(out)
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        isize::try_from(i64::decode(input)?).map_err(|_| invalid_data("isize out of range"))
+    }
+}
+
+impl BinaryCodec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8)
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("invalid bool")),
+        }
+    }
+}
+
+impl BinaryCodec for char {
+    fn encode(&self, out: &mut Vec<u8>) {
+        u32::from(*self).encode(out)
+    }
+
+    fn decode(input: &mut &[u8]) -> io::Result<Self> {
+        char::from_u32(u32::decode(input)?).ok_or_else(|| invalid_data("invalid char"))
+    }
+}
+
+impl<T: BinaryCodec, C: Capacity> Chunk<T, C> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for value in &self.elements {
+            value.encode(out);
+        }
+    }
+
+    fn decode(mut input: &[u8], len: usize, chunk_size: C) -> io::Result<Self> {
+        if len > chunk_size.get() {
+            return Err(invalid_data("chunk is longer than the chunk size"))
+        }
+        let mut chunk = Chunk::with_capacity(chunk_size, len);
+        for _ in 0..len {
+            chunk.elements.push_back(T::decode(&mut input)?);
+        }
+        match input.is_empty() {
+            true => Ok(chunk),
+            false => Err(invalid_data("trailing bytes after chunk")),
+        }
+    }
+}
+
+/// CRC-32 (IEEE) lookup table.
+#[cfg(feature = "checksums")]
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+#[cfg(feature = "checksums")]
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| CRC32_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8))
+}
+
+#[cfg(feature = "checksums")]
+fn verify_checksum(bytes: &[u8], checksum: u32) -> io::Result<()> {
+    match crc32(bytes) == checksum {
+        true => Ok(()),
+        false => Err(invalid_data("chunk checksum mismatch")),
+    }
+}
+
+/// Checksums are CRC-32 of the binary encoding of every chunk, computed on demand.
+#[cfg(feature = "checksums")]
+impl<T: BinaryCodec, C: Capacity> ChunkListBase<T, C> {
+    pub fn chunk_checksums(&self) -> Vec<u32> {
+        let mut buffer = Vec::new();
+        self.chunks.iter()
+            .map(|chunk| {
+                buffer.clear();
+                chunk.encode(&mut buffer);
+                crc32(&buffer)
+            })
+            .collect()
+    }
+
+    /// Compares against checksums taken earlier with `chunk_checksums`, returning the indices of
+    /// the chunks which differ. Chunks missing from either side count as differing.
+    pub fn verify(&self, checksums: &[u32]) -> Result<(), Vec<usize>> {
+        let current = self.chunk_checksums();
+        let mismatches: Vec<usize> = (0..current.len().max(checksums.len()))
+            .filter(|&chunk_i| current.get(chunk_i) != checksums.get(chunk_i))
+            .collect();
+        match mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(mismatches),
+        }
+    }
+}
+
+/// Binary format of `write_to`: the elements count as `u64` and a flags byte, then every
+/// non-empty chunk as its length as `u32`, the length of its encoding in bytes as `u64` and
+/// the encoded elements. With the `checksums` feature flag bit 0 is set and every chunk is
+/// followed by the CRC-32 of its encoding, which `read_from` verifies. Builds without the
+/// feature skip the checksums. All integers are little endian.
+impl<T: BinaryCodec, const N: usize> ChunkList<T, N> {
+    /// Encodes one chunk at a time, so only a single chunk is ever buffered.
+    pub fn write_to(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_all(&(self.elements_count as u64).to_le_bytes())?;
+        writer.write_all(&[cfg!(feature = "checksums") as u8])?;
+        let mut buffer = Vec::new();
+        for chunk in self.chunks.iter().filter(|chunk| !chunk.is_empty()) {
+            buffer.clear();
+            chunk.encode(&mut buffer);
+            writer.write_all(&(chunk.len() as u32).to_le_bytes())?;
+            writer.write_all(&(buffer.len() as u64).to_le_bytes())?;
+            writer.write_all(&buffer)?;
+            #[cfg(feature = "checksums")]
+            writer.write_all(&crc32(&buffer).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a list written by `write_to`, keeping its chunks as they were.
+    pub fn read_from(reader: &mut impl io::Read) -> io::Result<Self> {
+        fn read_array<const L: usize>(reader: &mut impl io::Read) -> io::Result<[u8; L]> {
+            let mut bytes = [0; L];
+            reader.read_exact(&mut bytes)?;
+            Ok(bytes)
+        }
+
+        let elements_count = usize::try_from(u64::from_le_bytes(read_array(reader)?))
+            .map_err(|_| invalid_data("elements count out of range"))?;
+        let checksums = match read_array::<1>(reader)? {
+            [0] => false,
+            [1] => true,
+            _ => return Err(invalid_data("unknown flags")),
+        };
+        let mut chunk_list = ChunkList::new();
+        let mut buffer = Vec::new();
+        while chunk_list.elements_count < elements_count {
+            let len = u32::from_le_bytes(read_array(reader)?) as usize;
+            let bytes_len = u64::from_le_bytes(read_array(reader)?);
+            if len == 0 || len > elements_count - chunk_list.elements_count {
+                return Err(invalid_data("invalid chunk length"))
+            }
+            buffer.clear();
+            if reader.take(bytes_len).read_to_end(&mut buffer)? as u64 != bytes_len {
+                return Err(io::ErrorKind::UnexpectedEof.into())
+            }
+            if checksums {
+                let checksum = u32::from_le_bytes(read_array(reader)?);
+                #[cfg(feature = "checksums")]
+                verify_checksum(&buffer, checksum)?;
+                #[cfg(not(feature = "checksums"))]
+                let _ = checksum;
+            }
+            let chunk = Chunk::decode(&buffer, len, Const)?;
+            chunk_list.elements_count += len;
+            chunk_list.chunks.push_back(chunk);
+        }
+        chunk_list.update_chunk_index();
+        Ok(chunk_list)
+    }
+}
+
+// --------------------
+// SPILL
+// --------------------
+/// Backend which keeps the chunks spilled by `SpillingChunkList`.
+pub trait SpillStore {
+    fn store(&mut self, key: u64, bytes: &[u8]) -> io::Result<()>;
+    /// Returns the bytes stored under `key` and forgets them.
+    fn take(&mut self, key: u64) -> io::Result<Vec<u8>>;
+}
+
+/// Stores chunks in one file. The space of taken chunks is reused by later chunks that fit in it.
+pub struct FileStore {
+    file: fs::File,
+    /// Removed on drop for stores created by `temp`.
+    temp_path: Option<PathBuf>,
+    slots: HashMap<u64, (u64, usize)>,
+    free: Vec<(u64, usize)>,
+    end: u64,
+}
+
+impl FileStore {
+    /// Uses the given file, truncating it.
+    pub fn new(file: fs::File) -> io::Result<Self> {
+        file.set_len(0)?;
+        Ok(FileStore { file, temp_path: None, slots: HashMap::new(), free: Vec::new(), end: 0 })
+    }
+
+    /// Creates a file in the temporary directory which is deleted on drop.
+    pub fn temp() -> io::Result<Self> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!("chunk-list-{}-{}.spill", std::process::id(), id));
+        let file = fs::OpenOptions::new().read(true).write(true).create_new(true).open(&path)?;
+        let mut store = FileStore::new(file)?;
+        store.temp_path = Some(path);
+        Ok(store)
+    }
+}
+
+impl Drop for FileStore {
+    fn drop(&mut self) {
+        if let Some(path) = &self.temp_path {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl SpillStore for FileStore {
+    fn store(&mut self, key: u64, bytes: &[u8]) -> io::Result<()> {
+        let offset = match self.free.iter().position(|&(_, len)| len >= bytes.len()) {
+            Some(free_i) => {
+                let (offset, len) = self.free.swap_remove(free_i);
+                if len > bytes.len() {
+                    self.free.push((offset + bytes.len() as u64, len - bytes.len()));
+                }
+                offset
+            }
+            None => {
+                self.end += bytes.len() as u64;
+                self.end - bytes.len() as u64
+            }
+        };
+        self.file.seek(io::SeekFrom::Start(offset))?;
+        self.file.write_all(bytes)?;
+        self.slots.insert(key, (offset, bytes.len()));
+        Ok(())
+    }
+
+    fn take(&mut self, key: u64) -> io::Result<Vec<u8>> {
+        let (offset, len) = self.slots.remove(&key).ok_or_else(|| invalid_data("unknown chunk key"))?;
+        let mut bytes = vec![0; len];
+        self.file.seek(io::SeekFrom::Start(offset))?;
+        self.file.read_exact(&mut bytes)?;
+        self.free.push((offset, len));
+        Ok(bytes)
+    }
+}
+
+/// Keeps chunks in memory compressed with LZ4. Used with `SpillingChunkList` the memory budget
+/// is the number of uncompressed chunks, the least recently used beyond it get compressed.
+#[cfg(feature = "compression")]
+#[derive(Default)]
+pub struct CompressedStore {
+    chunks: HashMap<u64, Vec<u8>>,
+}
+
+#[cfg(feature = "compression")]
+impl CompressedStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total size of the compressed chunks.
+    pub fn compressed_len(&self) -> usize {
+        self.chunks.values().map(Vec::len).sum()
+    }
+}
+
+#[cfg(feature = "compression")]
+impl SpillStore for CompressedStore {
+    fn store(&mut self, key: u64, bytes: &[u8]) -> io::Result<()> {
+        self.chunks.insert(key, lz4_flex::compress_prepend_size(bytes));
+        Ok(())
+    }
+
+    fn take(&mut self, key: u64) -> io::Result<Vec<u8>> {
+        let compressed = self.chunks.remove(&key).ok_or_else(|| invalid_data("unknown chunk key"))?;
+        lz4_flex::decompress_size_prepended(&compressed).map_err(|_| invalid_data("corrupted compressed chunk"))
+    }
+}
+
+enum SpillSlot<T, const N: usize> {
+    Resident { chunk: Chunk<T, Const<N>>, last_used: u64 },
+    Spilled {
+        key: u64,
+        len: usize,
+        /// Verified when the chunk is read back.
+        #[cfg(feature = "checksums")]
+        checksum: u32,
+    },
+}
+
+impl<T, const N: usize> SpillSlot<T, N> {
+    fn len(&self) -> usize {
+        match self {
+            SpillSlot::Resident { chunk, .. } => chunk.len(),
+            SpillSlot::Spilled { len, .. } => *len,
+        }
+    }
+}
+
+/// Chunk list which keeps at most `memory_budget` chunks in memory. The least recently used
+/// chunks beyond the budget are written to a `SpillStore` and read back when they are accessed,
+/// which is why accessors take `&mut self` and return `io::Result`.
+pub struct SpillingChunkList<T, const N: usize, S> {
+    slots: VecDeque<SpillSlot<T, N>>,
+    elements_count: usize,
+    store: S,
+    memory_budget: usize,
+    resident_count: usize,
+    clock: u64,
+    next_key: u64,
+    buffer: Vec<u8>,
+}
+
+impl<T: BinaryCodec, const N: usize, S: SpillStore> SpillingChunkList<T, N, S> {
+    /// Panics if `memory_budget == 0`.
+    pub fn new(store: S, memory_budget: usize) -> Self {
+        if memory_budget == 0 {
+            panic!("memory budget should be > 0 chunks")
+        }
+        SpillingChunkList {
+            slots: VecDeque::new(),
+            elements_count: 0,
+            store,
+            memory_budget,
+            resident_count: 0,
+            clock: 0,
+            next_key: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub fn elements_count(&self) -> usize {
+        self.elements_count
+    }
+
+    pub fn chunks_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn resident_chunks_count(&self) -> usize {
+        self.resident_count
+    }
+
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Spills chunks right away if more than `memory_budget` are resident.
+    ///
+    /// Panics if `memory_budget == 0`.
+    pub fn set_memory_budget(&mut self, memory_budget: usize) -> io::Result<()> {
+        if memory_budget == 0 {
+            panic!("memory budget should be > 0 chunks")
+        }
+        self.memory_budget = memory_budget;
+        self.enforce_budget(None)
+    }
+
+    /// Spills least recently used chunks other than `keep` until the budget is met.
+    fn enforce_budget(&mut self, keep: Option<usize>) -> io::Result<()> {
+        while self.resident_count > self.memory_budget {
+            let coldest = self.slots.iter()
+                .enumerate()
+                .filter(|&(slot_i, _)| Some(slot_i) != keep)
+                .filter_map(|(slot_i, slot)| match slot {
+                    SpillSlot::Resident { last_used, .. } => Some((*last_used, slot_i)),
+                    SpillSlot::Spilled { .. } => None,
+                })
+                .min();
+            let Some((_, slot_i)) = coldest else {
+                return Ok(())
+            };
+            let SpillSlot::Resident { chunk, .. } = &self.slots[slot_i] else {
+                unreachable!()
+            };
+            self.buffer.clear();
+            chunk.encode(&mut self.buffer);
+            let (key, len) = (self.next_key, chunk.len());
+            self.store.store(key, &self.buffer)?;
+            self.next_key += 1;
+            self.slots[slot_i] = SpillSlot::Spilled {
+                key,
+                len,
+                #[cfg(feature = "checksums")]
+                checksum: crc32(&self.buffer),
+            };
+            self.resident_count -= 1;
+        }
+        Ok(())
+    }
+
+    /// Reads back a spilled chunk, verifying its checksum with the `checksums` feature.
+    fn load(store: &mut S, slot: &SpillSlot<T, N>) -> io::Result<Chunk<T, Const<N>>> {
+        let SpillSlot::Spilled { key, len, .. } = slot else {
+            unreachable!()
+        };
+        let bytes = store.take(*key)?;
+        #[cfg(feature = "checksums")]
+        if let SpillSlot::Spilled { checksum, .. } = slot {
+            verify_checksum(&bytes, *checksum)?;
+        }
+        Chunk::decode(&bytes, *len, Const)
+    }
+
+    /// Loads the chunk if it is spilled and marks it as used.
+    fn fault_in(&mut self, slot_i: usize) -> io::Result<&mut Chunk<T, Const<N>>> {
+        self.clock += 1;
+        if let SpillSlot::Spilled { .. } = self.slots[slot_i] {
+            let chunk = Self::load(&mut self.store, &self.slots[slot_i])?;
+            self.slots[slot_i] = SpillSlot::Resident { chunk, last_used: self.clock };
+            self.resident_count += 1;
+            self.enforce_budget(Some(slot_i))?;
+        }
+        match &mut self.slots[slot_i] {
+            SpillSlot::Resident { chunk, last_used } => {
+                *last_used = self.clock;
+                Ok(chunk)
+            }
+            SpillSlot::Spilled { .. } => unreachable!(),
+        }
+    }
+
+    fn locate(&self, i: usize) -> Option<(usize, usize)> {
+        let mut count = 0;
+        for (slot_i, slot) in self.slots.iter().enumerate() {
+            if i < count + slot.len() {
+                return Some((slot_i, i - count))
+            }
+            count += slot.len();
+        }
+        None
+    }
+
+    pub fn get(&mut self, i: usize) -> io::Result<Option<&T>> {
+        let Some((slot_i, element_i)) = self.locate(i) else {
+            return Ok(None)
+        };
+        Ok(self.fault_in(slot_i)?.as_slice().get(element_i))
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> io::Result<Option<&mut T>> {
+        let Some((slot_i, element_i)) = self.locate(i) else {
+            return Ok(None)
+        };
+        Ok(self.fault_in(slot_i)?.as_mut_slice().get_mut(element_i))
+    }
+
+    pub fn push_back(&mut self, value: T) -> io::Result<()> {
+        if self.slots.back().is_none_or(|slot| slot.len() == N) {
+            self.slots.push_back(SpillSlot::Resident { chunk: Chunk::new(), last_used: 0 });
+            self.resident_count += 1;
+        }
+        let back_i = self.slots.len() - 1;
+        self.fault_in(back_i)?.push_back(value);
+        self.elements_count += 1;
+        self.enforce_budget(Some(back_i))
+    }
+
+    pub fn push_front(&mut self, value: T) -> io::Result<()> {
+        if self.slots.front().is_none_or(|slot| slot.len() == N) {
+            self.slots.push_front(SpillSlot::Resident { chunk: Chunk::new(), last_used: 0 });
+            self.resident_count += 1;
+        }
+        self.fault_in(0)?.push_front(value);
+        self.elements_count += 1;
+        self.enforce_budget(Some(0))
+    }
+
+    pub fn pop_back(&mut self) -> io::Result<Option<T>> {
+        let Some(back_i) = self.slots.len().checked_sub(1) else {
+            return Ok(None)
+        };
+        let value = self.fault_in(back_i)?.pop_back();
+        self.remove_if_empty(back_i);
+        Ok(value)
+    }
+
+    pub fn pop_front(&mut self) -> io::Result<Option<T>> {
+        if self.slots.is_empty() {
+            return Ok(None)
+        }
+        let value = self.fault_in(0)?.pop_front();
+        self.remove_if_empty(0);
+        Ok(value)
+    }
+
+    /// Called after an element was popped from the resident chunk `slot_i`.
+    fn remove_if_empty(&mut self, slot_i: usize) {
+        self.elements_count -= 1;
+        if self.slots[slot_i].len() == 0 {
+            self.slots.remove(slot_i);
+            self.resident_count -= 1;
+        }
+    }
+
+    /// Loads all spilled chunks back, the budget doesn't apply to the result.
+    pub fn into_chunk_list(mut self) -> io::Result<ChunkList<T, N>> {
+        let mut chunk_list = ChunkList::new();
+        for slot in mem::take(&mut self.slots) {
+            let chunk = match slot {
+                SpillSlot::Resident { chunk, .. } => chunk,
+                spilled => Self::load(&mut self.store, &spilled)?,
+            };
+            chunk_list.elements_count += chunk.len();
+            chunk_list.chunks.push_back(chunk);
+        }
+        Ok(chunk_list)
+    }
+}
+
+// --------------------
+// CHUNK STRING
+// --------------------
+/// Rope of UTF-8 text, stored as a byte chunk list. Code points may be split between chunks.
+/// Positions are byte indices which should lie on char boundaries, like in `String`.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct ChunkString<const N: usize> {
+    bytes: ChunkList<u8, N>,
+}
+
+fn is_char_boundary_byte(byte: u8) -> bool {
+    byte & 0b1100_0000 != 0b1000_0000
+}
+
+impl<const N: usize> ChunkString<N> {
+    pub fn new() -> Self {
+        ChunkString { bytes: ChunkList::new() }
+    }
+
+    pub fn as_bytes(&self) -> &ChunkList<u8, N> {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> ChunkList<u8, N> {
+        self.bytes
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.elements_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// O(len), every byte is looked at.
+    pub fn chars_count(&self) -> usize {
+        self.bytes.iter().filter(|&&byte| is_char_boundary_byte(byte)).count()
+    }
+
+    pub fn is_char_boundary(&self, i: usize) -> bool {
+        match self.bytes.get(i) {
+            Some(&byte) => is_char_boundary_byte(byte),
+            None => i == self.len(),
+        }
+    }
+
+    fn assert_char_boundary(&self, i: usize) {
+        if !self.is_char_boundary(i) {
+            panic!("byte index {} is not a char boundary of a string of len {}", i, self.len())
+        }
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.push_str(c.encode_utf8(&mut [0; 4]))
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        self.bytes.extend_from_copy_slice(s.as_bytes())
+    }
+
+    /// Splits the list at `i` once, so the cost is O(chunks + s.len()).
+    ///
+    /// Panics if `i` is not a char boundary.
+    pub fn insert_str(&mut self, i: usize, s: &str) {
+        self.assert_char_boundary(i);
+        let mut tail = self.bytes.split_off(i);
+        self.bytes.extend_from_copy_slice(s.as_bytes());
+        self.bytes.append(&mut tail);
+    }
+
+    pub fn insert(&mut self, i: usize, c: char) {
+        self.insert_str(i, c.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Removes the bytes in range.
+    ///
+    /// Panics if the range is out of bounds or its ends are not char boundaries.
+    pub fn remove_range(&mut self, range: impl RangeBounds<usize>) {
+        let (start, end) = range_to_indices(range, self.len());
+        self.assert_char_boundary(start);
+        self.assert_char_boundary(end);
+        self.bytes.drain(start..end);
+    }
+
+    pub fn clear(&mut self) {
+        self.bytes.clear()
+    }
+
+    pub fn as_chunk_str(&self) -> ChunkStr<'_, N> {
+        ChunkStr { bytes: &self.bytes }
+    }
+
+    pub fn chars(&self) -> Chars<'_, N> {
+        Chars { bytes: self.bytes.iter() }
+    }
+
+    /// Char with index i, O(i).
+    pub fn char_at(&self, i: usize) -> Option<char> {
+        self.chars().nth(i)
+    }
+
+    /// Byte position of the char with index `char_i`, `len()` for one past the last char.
+    pub fn char_to_byte(&self, char_i: usize) -> Option<usize> {
+        let mut boundaries = self.bytes.iter()
+            .enumerate()
+            .filter(|(_, &byte)| is_char_boundary_byte(byte))
+            .map(|(i, _)| i)
+            .chain([self.len()]);
+        boundaries.nth(char_i)
+    }
+}
+
+/// Borrowed view of a byte chunk list which is known to be UTF-8, see `ChunkList::as_chunk_str`.
+#[derive(Clone, Copy)]
+pub struct ChunkStr<'a, const N: usize> {
+    bytes: &'a ChunkList<u8, N>,
+}
+
+impl<const N: usize> ChunkList<u8, N> {
+    /// Validates the bytes as UTF-8 chunk by chunk, without copying them into one buffer.
+    /// A code point split between chunks is put together from its pieces.
+    pub fn as_chunk_str(&self) -> Result<ChunkStr<'_, N>, ChunkListError> {
+        // Incomplete code point at the end of the previous chunks and where it starts.
+        let (mut pending, mut pending_len, mut pending_start) = ([0u8; 4], 0, 0);
+        let mut offset = 0;
+        for chunk in &self.chunks {
+            let mut rest = chunk.as_slice();
+            offset += rest.len();
+            if pending_len > 0 {
+                let width = pending[0].leading_ones() as usize;
+                let taken = (width - pending_len).min(rest.len());
+                pending[pending_len..pending_len + taken].copy_from_slice(&rest[..taken]);
+                pending_len += taken;
+                rest = &rest[taken..];
+                if pending_len < width {
+                    continue
+                }
+                if std::str::from_utf8(&pending[..width]).is_err() {
+                    return Err(ChunkListError::InvalidUtf8 { valid_up_to: pending_start })
+                }
+                pending_len = 0;
+            }
+            let rest_offset = offset - rest.len();
+            if let Err(error) = std::str::from_utf8(rest) {
+                let valid_up_to = rest_offset + error.valid_up_to();
+                if error.error_len().is_some() {
+                    return Err(ChunkListError::InvalidUtf8 { valid_up_to })
+                }
+                let incomplete = &rest[error.valid_up_to()..];
+                pending[..incomplete.len()].copy_from_slice(incomplete);
+                (pending_len, pending_start) = (incomplete.len(), valid_up_to);
+            }
+        }
+        match pending_len {
+            0 => Ok(ChunkStr { bytes: self }),
+            _ => Err(ChunkListError::InvalidUtf8 { valid_up_to: pending_start }),
+        }
+    }
+}
+
+impl<'a, const N: usize> ChunkStr<'a, N> {
+    pub fn as_bytes(&self) -> &'a ChunkList<u8, N> {
+        self.bytes
+    }
+
+    /// Length in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.elements_count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn chars(&self) -> Chars<'a, N> {
+        Chars { bytes: self.bytes.iter() }
+    }
+
+    pub fn char_indices(&self) -> CharIndices<'a, N> {
+        CharIndices { chars: self.chars(), len: self.len() }
+    }
+}
+
+impl<'a, const N: usize> fmt::Display for ChunkStr<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars().try_for_each(|c| fmt::Write::write_char(f, c))
+    }
+}
+
+impl<'a, const N: usize> fmt::Debug for ChunkStr<'a, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+/// Chars of a `ChunkString` or a `ChunkStr`, decoded across chunk boundaries.
+#[derive(Clone)]
+pub struct Chars<'a, const N: usize> {
+    bytes: Iter<'a, u8, Const<N>>,
+}
+
+/// Decodes one char from bytes which are known to be valid UTF-8.
+fn decode_char<'a>(bytes: &mut impl Iterator<Item = &'a u8>, first: u8) -> char {
+    let width = match first.leading_ones() {
+        0 => 1,
+        width => width as usize,
+    };
+    let mut buf = [first, 0, 0, 0];
+    for byte in &mut buf[1..width] {
+        *byte = *bytes.next().unwrap();
+    }
+    std::str::from_utf8(&buf[..width]).unwrap().chars().next().unwrap()
+}
+
+impl<'a, const N: usize> Iterator for Chars<'a, N> {
+    type Item = char;
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = *self.bytes.next()?;
+        Some(decode_char(&mut self.bytes, first))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bytes.len();
+        (len.div_ceil(4), Some(len))
+    }
+}
+
+impl<'a, const N: usize> FusedIterator for Chars<'a, N> {}
+
+/// Chars with their byte positions.
+#[derive(Clone)]
+pub struct CharIndices<'a, const N: usize> {
+    chars: Chars<'a, N>,
+    len: usize,
+}
+
+impl<'a, const N: usize> Iterator for CharIndices<'a, N> {
+    type Item = (usize, char);
+    fn next(&mut self) -> Option<Self::Item> {
+        let i = self.len - self.chars.bytes.len();
+        Some((i, self.chars.next()?))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chars.size_hint()
+    }
+}
+
+impl<'a, const N: usize> FusedIterator for CharIndices<'a, N> {}
+
+impl<const N: usize> fmt::Display for ChunkString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.chars().try_for_each(|c| fmt::Write::write_char(f, c))
+    }
+}
+
+impl<const N: usize> fmt::Debug for ChunkString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.to_string(), f)
+    }
+}
+
+impl<const N: usize> fmt::Write for ChunkString<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+impl<const N: usize> From<&str> for ChunkString<N> {
+    fn from(s: &str) -> Self {
+        let mut string = ChunkString::new();
+        string.push_str(s);
+        string
+    }
+}
+
+impl<const N: usize> PartialEq<&str> for ChunkString<N> {
+    fn eq(&self, other: &&str) -> bool {
+        self.bytes == other.as_bytes()
+    }
+}
+
+// --------------------
+// DYN CHUNK LIST
+// --------------------
+/// Reuses the chunks, the chunk size becomes N.
+impl<T, const N: usize> From<ChunkList<T, N>> for DynChunkList<T> {
+    fn from(chunk_list: ChunkList<T, N>) -> Self {
+        let mut dyn_chunk_list = DynChunkList::new(N);
+        dyn_chunk_list.chunks = chunk_list.chunks.into_iter()
+            .map(|mut chunk| Chunk { elements: mem::take(&mut chunk.elements), chunk_size: N })
+            .collect();
+        dyn_chunk_list.elements_count = chunk_list.elements_count;
+        dyn_chunk_list
+    }
+}
+
+impl<T, const N: usize> From<DynChunkList<T>> for ChunkList<T, N> {
+    fn from(dyn_chunk_list: DynChunkList<T>) -> Self {
+        dyn_chunk_list.into_iter().collect()
+    }
+}
+
+// --------------------
+// CONCURRENT
+// --------------------
+/// Chunk based containers which are shared between threads.
+/// The crate is written in safe Rust, so they are built on locks instead of atomics
+/// on the element slots, but a lock is taken once per chunk boundary crossing at most.
+pub mod concurrent {
+    use std::{
+        mem,
+        collections::VecDeque,
+        sync::{atomic::{AtomicUsize, Ordering}, Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError},
+    };
+    use super::{Chunk, Const};
+
+    /// Elements stay consistent if a thread panics while holding a lock, so poisoning is ignored.
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    struct Ring<T, const N: usize> {
+        state: Mutex<RingState<T, N>>,
+        not_full: Condvar,
+        capacity: usize,
+    }
+
+    struct RingState<T, const N: usize> {
+        chunks: VecDeque<Chunk<T, Const<N>>>,
+        producer_alive: bool,
+        consumer_alive: bool,
+    }
+
+    /// Creates a bounded single-producer single-consumer ring which moves elements in chunks.
+    /// The producer fills a chunk of its own without locking and publishes it once it is full,
+    /// at most `capacity` published chunks wait for the consumer.
+    ///
+    /// Panics if `capacity == 0`.
+    pub fn chunk_ring<T, const N: usize>(capacity: usize) -> (RingProducer<T, N>, RingConsumer<T, N>) {
+        if capacity == 0 {
+            panic!("ring capacity should be > 0")
+        }
+        let ring = Arc::new(Ring {
+            state: Mutex::new(RingState {
+                chunks: VecDeque::with_capacity(capacity),
+                producer_alive: true,
+                consumer_alive: true,
+            }),
+            not_full: Condvar::new(),
+            capacity,
+        });
+        (RingProducer { ring: Arc::clone(&ring), tail: Chunk::new() }, RingConsumer { ring })
+    }
+
+    /// Sending side of `chunk_ring`. A partially filled chunk is published by `flush`,
+    /// or on drop, in which case it may exceed the capacity.
+    pub struct RingProducer<T, const N: usize> {
+        ring: Arc<Ring<T, N>>,
+        tail: Chunk<T, Const<N>>,
+    }
+
+    /// Receiving side of `chunk_ring`.
+    pub struct RingConsumer<T, const N: usize> {
+        ring: Arc<Ring<T, N>>,
+    }
+
+    impl<T, const N: usize> RingProducer<T, N> {
+        /// Returns the value back if the own chunk is full and the ring has no room to publish it.
+        pub fn try_push(&mut self, value: T) -> Result<(), T> {
+            if self.tail.is_full() && !self.try_publish() {
+                return Err(value)
+            }
+            self.tail.push_back(value);
+            Ok(())
+        }
+
+        /// Blocks while the ring is full. Values pushed after the consumer is dropped are discarded.
+        pub fn push(&mut self, value: T) {
+            if self.tail.is_full() {
+                self.publish();
+            }
+            self.tail.push_back(value);
+        }
+
+        /// Publishes the partially filled chunk, blocking while the ring is full.
+        pub fn flush(&mut self) {
+            if !self.tail.is_empty() {
+                self.publish();
+            }
+        }
+
+        fn try_publish(&mut self) -> bool {
+            let mut state = lock(&self.ring.state);
+            if state.chunks.len() == self.ring.capacity && state.consumer_alive {
+                return false
+            }
+            let chunk = mem::take(&mut self.tail);
+            if state.consumer_alive {
+                state.chunks.push_back(chunk);
+            }
+            true
+        }
+
+        fn publish(&mut self) {
+            let mut state = lock(&self.ring.state);
+            while state.chunks.len() == self.ring.capacity && state.consumer_alive {
+                state = self.ring.not_full.wait(state).unwrap_or_else(PoisonError::into_inner);
+            }
+            let chunk = mem::take(&mut self.tail);
+            if state.consumer_alive {
+                state.chunks.push_back(chunk);
+            }
+        }
+    }
+
+    impl<T, const N: usize> Drop for RingProducer<T, N> {
+        fn drop(&mut self) {
+            let mut state = lock(&self.ring.state);
+            if !self.tail.is_empty() && state.consumer_alive {
+                state.chunks.push_back(mem::take(&mut self.tail));
+            }
+            state.producer_alive = false;
+        }
+    }
+
+    impl<T, const N: usize> RingConsumer<T, N> {
+        /// Takes the oldest published chunk without blocking.
+        pub fn pop_chunk(&mut self) -> Option<Chunk<T, Const<N>>> {
+            let chunk = lock(&self.ring.state).chunks.pop_front()?;
+            self.ring.not_full.notify_one();
+            Some(chunk)
+        }
+
+        /// True once the producer is dropped, chunks published before that can still be popped.
+        pub fn is_disconnected(&self) -> bool {
+            !lock(&self.ring.state).producer_alive
+        }
+    }
+
+    impl<T, const N: usize> Drop for RingConsumer<T, N> {
+        fn drop(&mut self) {
+            let mut state = lock(&self.ring.state);
+            state.consumer_alive = false;
+            state.chunks.clear();
+            self.ring.not_full.notify_one();
+        }
+    }
+
+    /// Append-only log which many threads push to concurrently without locks.
+    /// A push claims a slot with one atomic increment and then writes it once, readers see
+    /// the prefix of the log up to the first slot which is claimed but not written yet.
+    /// Chunks are allocated in segments, segment k holds 2^k chunks, so the chunks never move
+    /// and the segment table has a fixed size. Only threads which allocate the same segment
+    /// at the same time wait for each other.
+    pub struct AppendLog<T, const N: usize> {
+        segments: [OnceLock<Box<[OnceLock<T>]>>; usize::BITS as usize],
+        claimed: AtomicUsize,
+    }
+
+    impl<T, const N: usize> Default for AppendLog<T, N> {
+        fn default() -> Self {
+            AppendLog::new()
+        }
+    }
+
+    impl<T, const N: usize> AppendLog<T, N> {
+        pub fn new() -> Self {
+            AppendLog {
+                segments: std::array::from_fn(|_| OnceLock::new()),
+                claimed: AtomicUsize::new(0),
+            }
+        }
+
+        /// Segment and slot in it of the i-th element.
+        fn locate(i: usize) -> (usize, usize) {
+            let chunk_i = i / N + 1;
+            let segment_i = chunk_i.ilog2() as usize;
+            (segment_i, (chunk_i - (1 << segment_i)) * N + i % N)
+        }
+
+        fn slot(&self, i: usize) -> Option<&OnceLock<T>> {
+            let (segment_i, slot_i) = Self::locate(i);
+            self.segments[segment_i].get()?.get(slot_i)
+        }
+
+        /// Returns the index of the value.
+        pub fn push_back(&self, value: T) -> usize {
+            let i = self.claimed.fetch_add(1, Ordering::Relaxed);
+            let (segment_i, slot_i) = Self::locate(i);
+            let segment = self.segments[segment_i].get_or_init(|| {
+                (0..(N << segment_i)).map(|_| OnceLock::new()).collect()
+            });
+            if segment[slot_i].set(value).is_err() {
+                unreachable!("log slot {} is claimed twice", i)
+            }
+            i
+        }
+
+        /// Number of claimed slots, some of which may not be written yet.
+        pub fn claimed_len(&self) -> usize {
+            self.claimed.load(Ordering::Relaxed)
+        }
+
+        pub fn get(&self, i: usize) -> Option<&T> {
+            self.slot(i)?.get()
+        }
+
+        /// Iterates over the written prefix of the log, elements pushed meanwhile may be included.
+        pub fn iter(&self) -> AppendLogIter<'_, T, N> {
+            AppendLogIter { log: self, i: 0 }
+        }
+    }
+
+    pub struct AppendLogIter<'a, T, const N: usize> {
+        log: &'a AppendLog<T, N>,
+        i: usize,
+    }
+
+    impl<'a, T, const N: usize> Iterator for AppendLogIter<'a, T, N> {
+        type Item = &'a T;
+        fn next(&mut self) -> Option<Self::Item> {
+            let value = self.log.get(self.i)?;
+            self.i += 1;
+            Some(value)
+        }
+    }
+}
+
+// --------------------
+// PINNED
+// --------------------
+/// Chunk list which never moves its elements: an element keeps its address until it is removed,
+/// so references turned into raw pointers stay valid while the element is in the list.
+/// There is no insertion or removal in the middle and no compaction. Every chunk buffer is
+/// allocated full and is only filled in one direction, a push starts a new chunk whenever
+/// putting the element into the end chunk could make its ring buffer shift, which can leave
+/// chunks partially filled. Lookups are therefore linear in the number of chunks.
+pub struct PinnedChunkList<T, const N: usize> {
+    chunks: VecDeque<Chunk<T, Const<N>>>,
+    elements_count: usize,
+    /// Whether `push_back` may use the back chunk, it was started by `push_back`
+    /// and no element was popped from its front.
+    back_open: bool,
+    /// Whether `push_front` may use the front chunk, mirrors `back_open`.
+    front_open: bool,
+}
+
+impl<T, const N: usize> Default for PinnedChunkList<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for PinnedChunkList<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T, const N: usize> PinnedChunkList<T, N> {
+    pub fn new() -> Self {
+        PinnedChunkList { chunks: VecDeque::new(), elements_count: 0, back_open: false, front_open: false }
+    }
+
+    pub fn elements_count(&self) -> usize {
+        self.elements_count
+    }
+
+    pub fn chunks_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Returns the pushed element at its final address.
+    pub fn push_back(&mut self, value: T) -> &mut T {
+        if !self.back_open || self.chunks.back().is_none_or(Chunk::is_full) {
+            self.front_open &= !self.chunks.is_empty();
+            self.chunks.push_back(Chunk::new());
+            self.back_open = true;
+        }
+        let chunk = self.chunks.back_mut().unwrap();
+        chunk.push_back(value);
+        self.elements_count += 1;
+        chunk.elements.back_mut().unwrap()
+    }
+
+    /// Returns the pushed element at its final address.
+    pub fn push_front(&mut self, value: T) -> &mut T {
+        if !self.front_open || self.chunks.front().is_none_or(Chunk::is_full) {
+            self.back_open &= !self.chunks.is_empty();
+            self.chunks.push_front(Chunk::new());
+            self.front_open = true;
+        }
+        let chunk = self.chunks.front_mut().unwrap();
+        chunk.push_front(value);
+        self.elements_count += 1;
+        chunk.elements.front_mut().unwrap()
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let value = self.chunks.back_mut()?.pop_back();
+        self.elements_count -= 1;
+        // The front of a single chunk may now reach the start of its buffer.
+        self.front_open &= self.chunks.len() > 1;
+        if self.chunks.back().is_some_and(Chunk::is_empty) {
+            self.chunks.pop_back();
+            self.back_open = false;
+        }
+        value
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let value = self.chunks.front_mut()?.pop_front();
+        self.elements_count -= 1;
+        self.back_open &= self.chunks.len() > 1;
+        if self.chunks.front().is_some_and(Chunk::is_empty) {
+            self.chunks.pop_front();
+            self.front_open = false;
+        }
+        value
+    }
+
+    fn locate(&self, mut i: usize) -> Option<(usize, usize)> {
+        for (chunk_i, chunk) in self.chunks.iter().enumerate() {
+            if i < chunk.len() {
+                return Some((chunk_i, i))
+            }
+            i -= chunk.len();
+        }
+        None
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        let (chunk_i, element_i) = self.locate(i)?;
+        self.chunks[chunk_i].elements.get(element_i)
+    }
+
+    pub fn get_mut(&mut self, i: usize) -> Option<&mut T> {
+        let (chunk_i, element_i) = self.locate(i)?;
+        self.chunks[chunk_i].elements.get_mut(element_i)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.chunks.front()?.elements.front()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.chunks.back()?.elements.back()
+    }
+
+    pub fn iter(&self) -> Iter<'_, T, Const<N>> {
+        Iter {
+            chunks: self.chunks.iter(),
+            front: [].iter(),
+            back: [].iter(),
+            remaining: self.elements_count,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a PinnedChunkList<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, Const<N>>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T, const N: usize> Extend<T> for PinnedChunkList<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.push_back(value);
+        }
+    }
+}
+
+// --------------------
+// PERSISTENT
+// --------------------
+/// Chunk list whose chunks are shared between clones. Cloning copies only the spine of
+/// chunk pointers, and a modification copies just the chunk it touches if that chunk is
+/// still shared, so old versions stay valid and cheap to keep around.
+pub struct PersistentChunkList<T, const N: usize> {
+    chunks: VecDeque<Arc<Chunk<T, Const<N>>>>,
+    elements_count: usize,
+}
+
+impl<T, const N: usize> Clone for PersistentChunkList<T, N> {
+    fn clone(&self) -> Self {
+        PersistentChunkList {
+            chunks: self.chunks.clone(),
+            elements_count: self.elements_count,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for PersistentChunkList<T, N> {
+    fn default() -> Self {
+        PersistentChunkList::new()
+    }
+}
+
+impl<T, const N: usize> PersistentChunkList<T, N> {
+    pub fn new() -> Self {
+        PersistentChunkList {
+            chunks: VecDeque::new(),
+            elements_count: 0,
+        }
+    }
+
+    pub fn elements_count(&self) -> usize {
+        self.elements_count
+    }
+
+    pub fn chunks_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Number of chunks which are the same allocation in both lists.
+    pub fn shared_chunks_count(&self, other: &Self) -> usize {
+        self.chunks.iter().filter(|chunk| other.chunks.iter().any(|other| Arc::ptr_eq(chunk, other))).count()
+    }
+
+    fn locate(&self, i: usize) -> Option<(usize, usize)> {
+        let mut count = 0;
+        for (chunk_i, chunk) in self.chunks.iter().enumerate() {
+            if i < count + chunk.len() {
+                return Some((chunk_i, i - count))
+            }
+            count += chunk.len();
+        }
+        None
+    }
+
+    pub fn get(&self, i: usize) -> Option<&T> {
+        let (chunk_i, element_i) = self.locate(i)?;
+        self.chunks[chunk_i].as_slice().get(element_i)
+    }
+
+    pub fn front(&self) -> Option<&T> {
+        self.chunks.front()?.as_slice().first()
+    }
+
+    pub fn back(&self) -> Option<&T> {
+        self.chunks.back()?.as_slice().last()
+    }
+
+    pub fn iter(&self) -> PersistentIter<'_, T, N> {
+        PersistentIter {
+            chunks: self.chunks.iter(),
+            front: [].iter(),
+            back: [].iter(),
+            remaining: self.elements_count,
+        }
+    }
+}
+
+impl<T: Clone, const N: usize> PersistentChunkList<T, N> {
+    pub fn push_back(&mut self, value: T) {
+        if let Some(back) = self.chunks.back_mut().filter(|back| !back.is_full()) {
+            assert!(Arc::make_mut(back).push_back(value));
+        }
+        else {
+            let mut chunk = Chunk::new();
+            assert!(chunk.push_back(value));
+            self.chunks.push_back(Arc::new(chunk));
+        }
+        self.elements_count += 1;
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        if let Some(front) = self.chunks.front_mut().filter(|front| !front.is_full()) {
+            assert!(Arc::make_mut(front).push_front(value));
+        }
+        else {
+            let mut chunk = Chunk::new();
+            assert!(chunk.push_front(value));
+            self.chunks.push_front(Arc::new(chunk));
+        }
+        self.elements_count += 1;
+    }
+
+    pub fn pop_back(&mut self) -> Option<T> {
+        let back = self.chunks.back_mut()?;
+        let value = Arc::make_mut(back).pop_back();
+        if back.is_empty() {
+            self.chunks.pop_back();
+        }
+        self.elements_count -= 1;
+        value
+    }
+
+    pub fn pop_front(&mut self) -> Option<T> {
+        let front = self.chunks.front_mut()?;
+        let value = Arc::make_mut(front).pop_front();
+        if front.is_empty() {
+            self.chunks.pop_front();
+        }
+        self.elements_count -= 1;
+        value
+    }
+
+    /// Replaces the element at position i and returns the previous one.
+    ///
+    /// Panics if `i >= elements_count`.
+    pub fn set(&mut self, i: usize, value: T) -> T {
+        let Some((chunk_i, element_i)) = self.locate(i) else {
+            panic!("index out of bounds: the len is {} but the index is {}", self.elements_count, i)
+        };
+        mem::replace(&mut Arc::make_mut(&mut self.chunks[chunk_i]).as_mut_slice()[element_i], value)
+    }
+
+    /// Records the current state. Nothing is copied, the snapshot only holds on to the chunks,
+    /// and the chunks modified afterwards are the delta which gets copied on the first write.
+    pub fn snapshot(&self) -> Snapshot<T, N> {
+        Snapshot(self.clone())
+    }
+
+    /// Restores the state recorded by `snapshot`, undoing all modifications made since then.
+    pub fn rollback(&mut self, snapshot: Snapshot<T, N>) {
+        *self = snapshot.0;
+    }
+
+    /// Returns a new version with the element at position i replaced, `self` is left as it is.
+    ///
+    /// Panics if `i >= elements_count`.
+    pub fn update(&self, i: usize, value: T) -> Self {
+        let mut updated = self.clone();
+        updated.set(i, value);
+        updated
+    }
+}
+
+/// State of a `PersistentChunkList` to roll back to.
+pub struct Snapshot<T, const N: usize>(PersistentChunkList<T, N>);
+
+impl<T, const N: usize> Snapshot<T, N> {
+    /// Number of chunks which were copied by modifications of `list` since the snapshot.
+    pub fn changed_chunks_count(&self, list: &PersistentChunkList<T, N>) -> usize {
+        list.chunks_count() - list.shared_chunks_count(&self.0)
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for PersistentChunkList<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for PersistentChunkList<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.elements_count == other.elements_count && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for PersistentChunkList<T, N> {}
+
+impl<T, const N: usize> Index<usize> for PersistentChunkList<T, N> {
+    type Output = T;
+    fn index(&self, i: usize) -> &T {
+        match self.get(i) {
+            Some(value) => value,
+            None => panic!("index out of bounds: the len is {} but the index is {}", self.elements_count, i),
+        }
+    }
+}
+
+impl<T, const N: usize> From<ChunkList<T, N>> for PersistentChunkList<T, N> {
+    fn from(chunk_list: ChunkList<T, N>) -> Self {
+        PersistentChunkList {
+            elements_count: chunk_list.elements_count,
+            chunks: chunk_list.chunks.into_iter().map(Arc::new).collect(),
+        }
+    }
+}
+
+impl<T: Clone, const N: usize> From<PersistentChunkList<T, N>> for ChunkList<T, N> {
+    /// Chunks which are not shared anymore are moved, the others are cloned.
+    fn from(persistent: PersistentChunkList<T, N>) -> Self {
+        let mut chunk_list = ChunkList::new();
+        chunk_list.elements_count = persistent.elements_count;
+        chunk_list.chunks = persistent.chunks.into_iter().map(Arc::unwrap_or_clone).collect();
+        chunk_list
+    }
+}
+
+impl<T: Clone, const N: usize> FromIterator<T> for PersistentChunkList<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        ChunkList::from_iter(iter).into()
+    }
+}
+
+pub struct PersistentIter<'a, T, const N: usize> {
+    chunks: vec_deque::Iter<'a, Arc<Chunk<T, Const<N>>>>,
+    front: slice::Iter<'a, T>,
+    back: slice::Iter<'a, T>,
+    remaining: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for PersistentIter<'a, T, N> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.front.next() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next() {
+                Some(chunk) => self.front = chunk.as_slice().iter(),
+                None => {
+                    let value = self.back.next()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, T, const N: usize> DoubleEndedIterator for PersistentIter<'a, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(value) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some(value)
+            }
+            match self.chunks.next_back() {
+                Some(chunk) => self.back = chunk.as_slice().iter(),
+                None => {
+                    let value = self.front.next_back()?;
+                    self.remaining -= 1;
+                    return Some(value)
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, const N: usize> ExactSizeIterator for PersistentIter<'a, T, N> {}
+
+impl<'a, T, const N: usize> FusedIterator for PersistentIter<'a, T, N> {}
+
+// --------------------
+// MACRO
+// --------------------
+/// Creates a `ChunkList` like `vec!`.
+/// The chunk size is inferred from the context or given with `size = N;` first:
+/// `chunk_list![1, 2, 3]`, `chunk_list![0; 100]`, `chunk_list![size = 16; 0; 100]`.
+#[macro_export]
+macro_rules! chunk_list {
+    (size = $n:expr; $elem:expr; $count:expr) => {{
+        let mut chunk_list = $crate::ChunkList::<_, $n>::new();
+        chunk_list.resize($count, $elem);
+        chunk_list
+    }};
+    (size = $n:expr; $($x:expr),* $(,)?) => {
+        $crate::ChunkList::<_, $n>::from([$($x),*])
+    };
+    ($elem:expr; $count:expr) => {{
+        let mut chunk_list = $crate::ChunkList::new();
+        chunk_list.resize($count, $elem);
+        chunk_list
+    }};
+    ($($x:expr),* $(,)?) => {
+        $crate::ChunkList::from([$($x),*])
+    };
+}
+
+// --------------------
+// SERDE
+// --------------------
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::{fmt, marker::PhantomData};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use super::{Capacity, ChunkList, ChunkListBase};
+
+    impl<T: Serialize, C: Capacity> Serialize for ChunkListBase<T, C> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self)
+        }
+    }
+
+    struct ChunkListVisitor<T, const N: usize>(PhantomData<T>);
+
+    impl<'de, T: Deserialize<'de>, const N: usize> de::Visitor<'de> for ChunkListVisitor<T, N> {
+        type Value = ChunkList<T, N>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a sequence")
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut chunk_list = ChunkList::new();
+            while let Some(value) = seq.next_element()? {
+                chunk_list.push_back(value);
+            }
+            Ok(chunk_list)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>, const N: usize> Deserialize<'de> for ChunkList<T, N> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_seq(ChunkListVisitor(PhantomData))
+        }
+    }
+}
+
+// --------------------
+// RAYON
+// --------------------
+#[cfg(feature = "rayon")]
+pub use rayon_impls::{IntoParIter, ParIter, ParIterMut};
+
+/// Parallel iterators hand out whole chunks to rayon jobs, elements of one chunk are
+/// processed sequentially as a slice.
+#[cfg(feature = "rayon")]
+mod rayon_impls {
+    use std::collections::VecDeque;
+    use std::{cmp::Ordering, mem};
+    use rayon::iter::{plumbing::UnindexedConsumer, IntoParallelIterator, IntoParallelRefMutIterator, ParallelIterator};
+    use super::{merge_sorted_vecs, release_vec, Capacity, Chunk, ChunkListBase, RecountOnUnwind};
+
+    pub struct ParIter<'a, T, C> {
+        chunks: &'a VecDeque<Chunk<T, C>>,
+    }
+
+    pub struct ParIterMut<'a, T, C> {
+        chunks: &'a mut VecDeque<Chunk<T, C>>,
+    }
+
+    pub struct IntoParIter<T, C> {
+        chunks: VecDeque<Chunk<T, C>>,
+    }
+
+    /// Moves the elements out of a chunk which is dropped along with the iterator,
+    /// so that the `zeroize` feature wipes its buffer.
+    struct ChunkValues<T, C>(Chunk<T, C>);
+
+    impl<T, C: Capacity> Iterator for ChunkValues<T, C> {
+        type Item = T;
+        fn next(&mut self) -> Option<T> {
+            self.0.pop_front()
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            (self.0.len(), Some(self.0.len()))
+        }
+    }
+
+    impl<'a, T: Sync, C: Capacity> ParallelIterator for ParIter<'a, T, C> {
+        type Item = &'a T;
+        fn drive_unindexed<K: UnindexedConsumer<Self::Item>>(self, consumer: K) -> K::Result {
+            self.chunks.into_par_iter().flat_map_iter(Chunk::as_slice).drive_unindexed(consumer)
+        }
+    }
+
+    impl<'a, T: Send, C: Capacity> ParallelIterator for ParIterMut<'a, T, C> {
+        type Item = &'a mut T;
+        fn drive_unindexed<K: UnindexedConsumer<Self::Item>>(self, consumer: K) -> K::Result {
+            self.chunks.into_par_iter().flat_map_iter(Chunk::as_mut_slice).drive_unindexed(consumer)
+        }
+    }
+
+    impl<T: Send, C: Capacity> ParallelIterator for IntoParIter<T, C> {
+        type Item = T;
+        fn drive_unindexed<K: UnindexedConsumer<Self::Item>>(self, consumer: K) -> K::Result {
+            self.chunks.into_par_iter().flat_map_iter(ChunkValues).drive_unindexed(consumer)
+        }
+    }
+
+    impl<'a, T: Sync, C: Capacity> IntoParallelIterator for &'a ChunkListBase<T, C> {
+        type Iter = ParIter<'a, T, C>;
+        type Item = &'a T;
+        fn into_par_iter(self) -> Self::Iter {
+            ParIter { chunks: &self.chunks }
+        }
+    }
+
+    /// The chunk index is left untouched since elements are only modified in place.
+    impl<'a, T: Send, C: Capacity> IntoParallelIterator for &'a mut ChunkListBase<T, C> {
+        type Iter = ParIterMut<'a, T, C>;
+        type Item = &'a mut T;
+        fn into_par_iter(self) -> Self::Iter {
+            ParIterMut { chunks: &mut self.chunks }
+        }
+    }
+
+    impl<T: Send, C: Capacity> IntoParallelIterator for ChunkListBase<T, C> {
+        type Iter = IntoParIter<T, C>;
+        type Item = T;
+        fn into_par_iter(mut self) -> Self::Iter {
+            IntoParIter { chunks: mem::take(&mut self.chunks) }
+        }
+    }
+
+    impl<T: Send, C: Capacity> ChunkListBase<T, C> {
+        /// Parallel stable sort.
+        pub fn par_sort(&mut self)
+        where T: Ord {
+            self.par_sort_by(T::cmp)
+        }
+
+        /// Parallel stable sort with a comparator function.
+        /// The chunks are split into one run per thread and the runs are sorted concurrently,
+        /// then they are merged by a parallel multiway merge, see `par_merge`.
+        pub fn par_sort_by(&mut self, compare: impl Fn(&T, &T) -> Ordering + Sync) {
+            self.par_sort_runs(|run| run.sort_by(&compare), &compare)
+        }
+
+        /// Parallel unstable sort.
+        pub fn par_sort_unstable(&mut self)
+        where T: Ord {
+            self.par_sort_unstable_by(T::cmp)
+        }
+
+        /// Same as `par_sort_by`, but runs are sorted with an unstable sort.
+        pub fn par_sort_unstable_by(&mut self, compare: impl Fn(&T, &T) -> Ordering + Sync) {
+            self.par_sort_runs(|run| run.sort_unstable_by(&compare), &compare)
+        }
+
+        fn par_sort_runs(&mut self, sort: impl Fn(&mut Vec<T>) + Sync, compare: &(impl Fn(&T, &T) -> Ordering + Sync)) {
+            let mut list = RecountOnUnwind(self);
+            let parts = rayon::current_num_threads().min(list.chunks.len());
+            if parts <= 1 {
+                list.sort_by(compare);
+                return
+            }
+            let chunks_per_run = list.chunks.len().div_ceil(parts);
+            let mut chunks = mem::take(&mut list.chunks).into_iter();
+            let mut runs: Vec<Vec<T>> = (0..parts)
+                .map(|_| {
+                    let run_chunks: Vec<Chunk<T, C>> = chunks.by_ref().take(chunks_per_run).collect();
+                    let mut run = Vec::with_capacity(run_chunks.iter().map(Chunk::len).sum());
+                    for mut chunk in run_chunks {
+                        run.extend(chunk.elements.drain(..));
+                    }
+                    run
+                })
+                .filter(|run: &Vec<T>| !run.is_empty())
+                .collect();
+            runs.par_iter_mut().for_each(&sort);
+            let elements_count = list.elements_count;
+            list.elements_count = 0;
+            list.update_chunk_index();
+            for mut segment in par_merge(runs, parts, compare) {
+                list.extend(segment.drain(..));
+                release_vec(segment);
+            }
+            debug_assert_eq!(list.elements_count, elements_count);
+        }
+    }
+
+    /// Merges sorted runs into `parts` sorted segments which follow each other.
+    /// Splitters are picked from evenly spaced samples of every run, each run is cut
+    /// in front of the elements which are not less than a splitter, and then the pieces
+    /// between the same splitters are merged concurrently. All elements equal to a splitter
+    /// end up in the same segment and pieces are merged in run order, so the merge is stable.
+    fn par_merge<T: Send>(runs: Vec<Vec<T>>, parts: usize, compare: &(impl Fn(&T, &T) -> Ordering + Sync)) -> Vec<Vec<T>> {
+        let mut samples: Vec<&T> = runs.iter()
+            .flat_map(|run| (1..parts).map(move |k| &run[k * run.len() / parts]))
+            .collect();
+        samples.sort_by(|a, b| compare(a, b));
+        let splitters: Vec<&T> = (1..parts).map(|k| samples[k * samples.len() / parts]).collect();
+        let cuts: Vec<Vec<usize>> = runs.iter()
+            .map(|run| {
+                splitters.iter()
+                    .map(|splitter| run.partition_point(|value| compare(value, splitter) == Ordering::Less))
+                    .collect()
+            })
+            .collect();
+        let mut pieces: Vec<Vec<Vec<T>>> = (0..parts).map(|_| Vec::with_capacity(runs.len())).collect();
+        for (mut run, cuts) in runs.into_iter().zip(cuts) {
+            for (part, &cut) in cuts.iter().enumerate().rev() {
+                pieces[part + 1].push(run.split_off(cut));
+            }
+            pieces[0].push(run);
+        }
+        pieces.into_par_iter()
+            .map(|mut runs| {
+                while runs.len() > 1 {
+                    let mut merged = Vec::with_capacity(runs.len().div_ceil(2));
+                    let mut runs_iter = runs.into_iter();
+                    while let Some(a) = runs_iter.next() {
+                        match runs_iter.next() {
+                            Some(b) => merged.push(merge_sorted_vecs(a, b, &mut |x, y| compare(x, y))),
+                            None => merged.push(a),
+                        }
+                    }
+                    runs = merged;
+                }
+                runs.pop().unwrap_or_default()
+            })
+            .collect()
+    }
+}
+
+// --------------------
+// BYTES
+// --------------------
+/// `Buf` reads chunk by chunk like `io::BufRead`. There is no `BufMut`, it is an unsafe trait
+/// and the crate is written in safe Rust, `io::Write` covers writing instead.
+#[cfg(feature = "bytes")]
+mod bytes_impls {
+    use std::io::IoSlice;
+    use bytes::Buf;
+    use super::{Capacity, Chunk, ChunkListBase};
+
+    impl<C: Capacity> Buf for ChunkListBase<u8, C> {
+        fn remaining(&self) -> usize {
+            self.elements_count
+        }
+
+        fn chunk(&self) -> &[u8] {
+            self.chunks.iter().find(|chunk| !chunk.is_empty()).map_or(&[], Chunk::as_slice)
+        }
+
+        fn chunks_vectored<'a>(&'a self, dst: &mut [IoSlice<'a>]) -> usize {
+            let slices = self.chunks.iter().filter(|chunk| !chunk.is_empty()).map(|chunk| IoSlice::new(chunk.as_slice()));
+            dst.iter_mut().zip(slices).map(|(slot, slice)| *slot = slice).count()
+        }
+
+        /// Panics if `cnt > remaining()`.
+        fn advance(&mut self, cnt: usize) {
+            if cnt > self.elements_count {
+                panic!("cannot advance past remaining (cnt is {}, remaining is {})", cnt, self.elements_count)
+            }
+            self.truncate_front(self.elements_count - cnt);
+        }
+    }
+}
+
+// --------------------
+// TESTING
+// --------------------
+/// `Arbitrary` impl and proptest strategies for testing code which embeds a `ChunkList`.
+/// Lists are built by random pushes at both ends, insertions and removals, so their chunks are
+/// filled unevenly like in a list that has been in use.
+#[cfg(feature = "testing")]
+pub mod testing {
+    use std::fmt;
+    use arbitrary::{Arbitrary, Unstructured};
+    use proptest::{collection, prelude::*, sample::Index};
+    use super::{Capacity, ChunkList, ChunkListBase};
+
+    /// Applies one building step, `position` is reduced modulo the valid range.
+    fn apply<T, C: Capacity>(list: &mut ChunkListBase<T, C>, value: T, operation: u8, position: usize) {
+        match operation % 4 {
+            0 => list.push_back(value),
+            1 => list.push_front(value),
+            2 => list.insert(position % (list.elements_count() + 1), value),
+            _ => {
+                list.push_back(value);
+                list.remove(position % list.elements_count());
+            }
+        }
+    }
+
+    impl<'a, T: Arbitrary<'a>, const N: usize> Arbitrary<'a> for ChunkList<T, N> {
+        fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+            let mut list = ChunkList::new();
+            for _ in 0..u.arbitrary_len::<(T, u8, usize)>()? {
+                apply(&mut list, T::arbitrary(u)?, u8::arbitrary(u)?, usize::arbitrary(u)?);
+            }
+            Ok(list)
+        }
+    }
+
+    /// Lists built from `steps` random building steps, so they hold at most that many elements.
+    pub fn chunk_list<T: fmt::Debug, const N: usize>(
+        element: impl Strategy<Value = T>,
+        steps: impl Into<collection::SizeRange>,
+    ) -> impl Strategy<Value = ChunkList<T, N>> {
+        collection::vec((element, any::<u8>(), any::<Index>()), steps).prop_map(|steps| {
+            let mut list = ChunkList::new();
+            for (value, operation, position) in steps {
+                let len = list.elements_count() + 1;
+                apply(&mut list, value, operation, position.index(len));
+            }
+            list
+        })
+    }
+}
+
+// --------------------
+// ZEROIZE
+// --------------------
+/// With the feature on, every buffer the list releases has its elements dropped and is then
+/// wiped over its whole capacity: chunks dropped with the list or when the pool is full, old
+/// buffers of chunks which grow, shrink or get recentered, and the temporary vectors of
+/// `sort_by` and `par_sort_by`. Slots of popped and removed elements are wiped too, at the
+/// latest when their chunk is released. `zeroize` wipes every chunk buffer in place, pooled
+/// chunks included, and leaves the list empty.
+///
+/// Not covered are buffers handed over to the caller (`into_vec`, `into_raw_parts`,
+/// `pop_chunk_back` and the like), encoded copies (`write_to` and spill stores) and heap memory
+/// owned by the elements themselves, which is up to `T`.
+#[cfg(feature = "zeroize")]
+mod zeroize_impls {
+    use std::{collections::VecDeque, mem};
+    use zeroize::{Zeroize, ZeroizeOnDrop};
+    use super::{recycle_chunk, Capacity, Chunk, ChunkListBase};
+
+    pub(super) fn wipe<T>(mut vec: Vec<T>) {
+        vec.clear();
+        vec.spare_capacity_mut().zeroize();
+    }
+
+    impl<T, C> Drop for Chunk<T, C> {
+        fn drop(&mut self) {
+            wipe(Vec::from(mem::take(&mut self.elements)));
+        }
+    }
+
+    impl<T: Zeroize, C: Capacity> Zeroize for ChunkListBase<T, C> {
+        fn zeroize(&mut self) {
+            for chunk in self.chunks.iter_mut().chain(&mut self.spare_chunks) {
+                // Both conversions reuse the buffer, the `Vec` impl wipes its spare capacity.
+                let mut elements = Vec::from(mem::take(&mut chunk.elements));
+                elements.zeroize();
+                chunk.elements = VecDeque::from(elements);
+            }
+            for chunk in mem::take(&mut self.chunks) {
+                recycle_chunk(&mut self.spare_chunks, self.chunk_pool_limit, chunk);
+            }
+            self.elements_count = 0;
+            self.update_chunk_index();
+        }
+    }
+
+    impl<T: ZeroizeOnDrop, C: Capacity> ZeroizeOnDrop for ChunkListBase<T, C> {}
+}
+
+fn main() {
+
+}
+
+#[cfg(test)]
+mod test {
+    use super::ChunkList;
+    
+    #[test]
+    fn push_pop_front() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_front(1);
+        list.push_front(2);
+        list.push_front(3);
+        assert_eq!(list.pop_front(), Some(3));
+        assert_eq!(list.pop_front(), Some(2));
+        assert_eq!(list.pop_front(), Some(1));
+        assert_eq!(list.pop_front(), None);
+    }
+
+    #[test]
+    fn peek() {
+        let mut list = ChunkList::<i32, 2>::new();
+        assert_eq!(list.get(0), None);
+        list.push_front(3);
+        list.push_front(2);
+        list.push_front(1);
+        assert_eq!(list.get(0), Some(&1));
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_front(1);
+        list.push_front(2);
+        list.push_front(3);
+
+        let mut iter = list.into_iter();
+        assert_eq!(iter.next(), Some(3));
+        assert_eq!(iter.next(), Some(2));
+        assert_eq!(iter.next(), Some(1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_front(1);
+        list.push_front(2);
+        list.push_front(3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back(4);
+        list.push_back(5);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next_back(), Some(&5));
+        assert_eq!(iter.next(), Some(&1));
+        assert_eq!(iter.next_back(), Some(&4));
+        assert_eq!(iter.next_back(), Some(&3));
+        assert_eq!(iter.next(), Some(&2));
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next_back(), None);
+
+        let reversed: Vec<i32> = list.into_iter().rev().collect();
+        assert_eq!(reversed, vec![5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn iter_len() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 3);
+        iter.next();
+        assert_eq!(iter.len(), 2);
+        iter.next_back();
+        assert_eq!(iter.size_hint(), (1, Some(1)));
+
+        let mut iter_mut = list.iter_mut();
+        iter_mut.next_back();
+        assert_eq!(iter_mut.len(), 2);
+
+        let mut into_iter = list.into_iter();
+        into_iter.next();
+        assert_eq!(into_iter.len(), 2);
+    }
+
+    #[test]
+    fn for_loops() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        for value in &mut list {
+            *value += 1;
+        }
+        let mut sum = 0;
+        for value in &list {
+            sum += value;
+        }
+        assert_eq!(sum, 9);
+        let mut values = Vec::new();
+        for value in list {
+            values.push(value);
+        }
+        assert_eq!(values, vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn collect_extend() {
+        let mut list: ChunkList<i32, 4> = (0..10).collect();
+        assert_eq!(list.elements_count(), 10);
+        assert_eq!(list.chunks_count(), 3);
+        list.extend(10..12);
+        list.extend(&[12, 13]);
+        assert_eq!(list.chunks_count(), 4);
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), (0..14).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn index() {
+        let mut list: ChunkList<i32, 2> = (0..5).collect();
+        assert_eq!(list[0], 0);
+        assert_eq!(list[3], 3);
+        list[4] = 40;
+        assert_eq!(list[4], 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn index_out_of_bounds() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let _ = list[5];
+    }
+
+    #[test]
+    fn clone() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let cloned = list.clone();
+        assert_eq!(cloned.chunks_count(), 3);
+        assert_eq!(cloned.into_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+
+        let mut longer: ChunkList<i32, 2> = (10..20).collect();
+        longer.clone_from(&list);
+        assert_eq!(longer.elements_count(), 5);
+        assert_eq!(longer.chunks_count(), 3);
+        assert_eq!(longer.into_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+
+        let mut shorter: ChunkList<i32, 2> = (10..11).collect();
+        shorter.clone_from(&list);
+        assert_eq!(shorter.into_iter().collect::<Vec<_>>(), vec![0, 1, 2, 3, 4]);
+
+        use super::{DynChunkList, GrowingChunkPolicy};
+        static POLICY: GrowingChunkPolicy = GrowingChunkPolicy { initial_capacity: 1 };
+        let mut source = DynChunkList::new(3);
+        source.set_chunk_policy(&POLICY);
+        source.set_min_fill_factor(0.5);
+        source.set_chunk_pool_limit(7);
+        source.set_chunk_index(true);
+        source.extend(0..8);
+        let cloned = source.clone();
+        let mut target = DynChunkList::new(2);
+        target.extend(10..20);
+        target.clone_from(&source);
+        for list in [&cloned, &target] {
+            assert_eq!(list, &source);
+            assert_eq!(list.chunk_capacity(), 3);
+            assert!(std::ptr::addr_eq(list.chunk_policy(), &POLICY));
+            assert_eq!(list.min_fill_factor(), source.min_fill_factor());
+            assert_eq!(list.chunk_pool_limit(), 7);
+            assert!(list.has_chunk_index());
+            assert_eq!(list.chunks().map(<[_]>::len).collect::<Vec<_>>(), vec![3, 3, 2]);
+        }
+        target.pop_front();
+        target.insert(4, 100);
+        assert_eq!(target, [1, 2, 3, 4, 100, 5, 6, 7]);
+    }
+
+    #[test]
+    fn debug() {
+        let list: ChunkList<i32, 2> = (0..3).collect();
+        assert_eq!(format!("{:?}", list), "[0, 1, 2]");
+        assert_eq!(format!("{:?}", list.chunks[1]), "[2]");
+    }
+
+    #[test]
+    fn eq() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let mut other = ChunkList::<i32, 3>::new();
+        for value in (0..5).rev() {
+            other.push_front(value);
+        }
+        assert_eq!(list, other);
+        assert_eq!(list, vec![0, 1, 2, 3, 4]);
+        assert_eq!(list, [0, 1, 2, 3, 4]);
+        assert_eq!(list, &[0, 1, 2, 3, 4][..]);
+        assert_ne!(list, vec![0, 1, 2, 3]);
+        assert_ne!(list, vec![0, 1, 2, 3, 5]);
+        other.pop_back();
+        assert_ne!(list, other);
+    }
+
+    #[test]
+    fn ord() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let mut other = ChunkList::<i32, 2>::new();
+        for value in (0..5).rev() {
+            other.push_front(value);
+        }
+        assert_eq!(list.cmp(&other), std::cmp::Ordering::Equal);
+        other.pop_back();
+        assert!(other < list);
+        other.push_back(5);
+        assert!(other > list);
+
+        let floats: ChunkList<f64, 2> = [1.0, f64::NAN].into_iter().collect();
+        assert_eq!(floats.partial_cmp(&floats), None);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn rayon() {
+        use rayon::prelude::*;
+        let mut list: ChunkList<u64, 16> = (0..1000).collect();
+        assert_eq!(list.par_iter().sum::<u64>(), (0..1000u64).sum::<u64>());
+        list.par_iter_mut().for_each(|value| *value *= 2);
+        assert!(list.iter().copied().eq((0..1000).map(|value| value * 2)));
+        let mut values: Vec<u64> = list.into_par_iter().filter(|value| value % 3 == 0).collect();
+        values.sort();
+        assert_eq!(values, (0..1000).map(|value| value * 2).filter(|value| value % 3 == 0).collect::<Vec<_>>());
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_sort() {
+        let values: Vec<u64> = (0..5000u64).map(|i| i * 7919 % 1009).collect();
+        let mut list: ChunkList<(u64, usize), 16> = values.iter().map(|&value| value / 4).zip(0..).collect();
+        let mut expected = list.clone();
+        expected.sort_by_key(|&(key, _)| key);
+        list.par_sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(list, expected);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        let mut list: ChunkList<(u64, usize), 16> = values.iter().map(|&value| value / 4).zip(0..).collect();
+        pool.install(|| list.par_sort_by(|a, b| a.0.cmp(&b.0)));
+        assert_eq!(list, expected);
+        assert!(list.occupancy_stats().unwrap().min > 0);
+        assert_eq!(list.check_invariants(), Ok(()));
+
+        let mut list: ChunkList<u64, 16> = values.iter().copied().collect();
+        pool.install(|| list.par_sort_unstable());
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(list, sorted);
+        let mut list = ChunkList::<u64, 16>::from_iter([3, 1, 2]);
+        list.par_sort();
+        assert_eq!(list, [1, 2, 3]);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_sort_panicking_comparator() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(4).build().unwrap();
+        for unstable in [false, true] {
+            let mut list: ChunkList<u64, 16> = (0..1000).rev().collect();
+            let calls = AtomicUsize::new(0);
+            let compare = |a: &u64, b: &u64| {
+                if calls.fetch_add(1, Ordering::Relaxed) == 500 {
+                    panic!("comparator panicked")
+                }
+                a.cmp(b)
+            };
+            let result = catch_unwind(AssertUnwindSafe(|| pool.install(|| match unstable {
+                false => list.par_sort_by(compare),
+                true => list.par_sort_unstable_by(compare),
+            })));
+            assert!(result.is_err());
+            assert_eq!(list.elements_count(), list.iter().count());
+            assert_eq!(list.check_invariants(), Ok(()));
+        }
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bytes_buf() {
+        use std::io::IoSlice;
+        use bytes::Buf;
+        let mut list = ChunkList::<u8, 4>::from_iter(1..=10);
+        assert_eq!(list.remaining(), 10);
+        assert_eq!(list.chunk(), [1, 2, 3, 4]);
+        assert_eq!(list.get_u16(), 0x0102);
+        assert_eq!(list.chunk(), [3, 4]);
+        let mut slices = [IoSlice::new(&[]); 2];
+        assert_eq!(list.chunks_vectored(&mut slices), 2);
+        assert_eq!((&*slices[0], &*slices[1]), (&[3, 4][..], &[5, 6, 7, 8][..]));
+        list.advance(3);
+        assert_eq!(list.copy_to_bytes(4).as_ref(), [6, 7, 8, 9]);
+        assert_eq!(list.get_u8(), 10);
+        assert!(!list.has_remaining());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let json = serde_json::to_string(&list).unwrap();
+        assert_eq!(json, "[0,1,2,3,4]");
+        let other: ChunkList<i32, 3> = serde_json::from_str(&json).unwrap();
+        assert_eq!(other.chunks_count(), 2);
+        assert_eq!(list, other);
+    }
+
+    #[test]
+    fn remove() {
+        let mut list: ChunkList<i32, 2> = (0..6).collect();
+        assert_eq!(list.remove(3), Some(3));
+        assert_eq!(list.remove(2), Some(2));
+        assert_eq!(list.chunks_count(), 2);
+        assert_eq!(list.remove(4), None);
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list, vec![0, 1, 4, 5]);
+    }
+
+    #[test]
+    fn insert() {
+        let mut list: ChunkList<i32, 4> = (0..8).collect();
+        list.insert(2, 10);
+        assert_eq!(list.chunks_count(), 3);
+        assert_eq!(list, vec![0, 1, 10, 2, 3, 4, 5, 6, 7]);
+        list.insert(3, 11);
+        assert_eq!(list.chunks_count(), 3);
+        list.insert(10, 12);
+        list.insert(0, 13);
+        assert_eq!(list, vec![13, 0, 1, 10, 11, 2, 3, 4, 5, 6, 7, 12]);
+        assert_eq!(list.elements_count(), 12);
+
+        let mut list = ChunkList::<i32, 1>::new();
+        list.insert(0, 1);
+        list.insert(0, 0);
+        list.insert(1, 2);
+        assert_eq!(list, vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut list: ChunkList<i32, 2> = (0..5).collect();
+        *list.get_mut(3).unwrap() = 30;
+        assert_eq!(list.get_mut(5), None);
+        assert_eq!(list, vec![0, 1, 2, 30, 4]);
+    }
+
+    #[test]
+    fn front_back() {
+        let mut list = ChunkList::<i32, 2>::new();
+        assert_eq!(list.front(), None);
+        assert_eq!(list.back_mut(), None);
+        list.extend(0..5);
+        assert_eq!(list.front(), Some(&0));
+        assert_eq!(list.back(), Some(&4));
+        *list.front_mut().unwrap() = 10;
+        *list.back_mut().unwrap() = 40;
+        assert_eq!(list, vec![10, 1, 2, 3, 40]);
+    }
+
+    #[test]
+    fn retain() {
+        let mut list: ChunkList<i32, 2> = (0..10).collect();
+        list.retain(|value| *value % 4 == 0 || *value == 7);
+        assert_eq!(list, vec![0, 4, 7, 8]);
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list.chunks_count(), 4);
+        list.retain_mut(|value| {
+            *value += 1;
+            *value != 8
+        });
+        assert_eq!(list, vec![1, 5, 9]);
+        assert_eq!(list.chunks_count(), 3);
+    }
+
+    #[test]
+    fn drain() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        let drained: Vec<i32> = list.drain(2..8).collect();
+        assert_eq!(drained, vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(list, vec![0, 1, 8, 9]);
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list.chunks_count(), 3);
+
+        let mut drain = list.drain(1..=2);
+        assert_eq!(drain.next(), Some(1));
+        drop(drain);
+        assert_eq!(list, vec![0, 9]);
+
+        assert_eq!(list.drain(1..1).count(), 0);
+        assert_eq!(list.drain(..).count(), 2);
+        assert_eq!(list.chunks_count(), 0);
+    }
+
+    #[test]
+    fn splice() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        let removed: Vec<i32> = list.splice(2..8, [20, 21]).collect();
+        assert_eq!(removed, vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(list, vec![0, 1, 20, 21, 8, 9]);
+        assert_eq!(list.elements_count(), 6);
+        assert_eq!(list.splice(6.., 10..15).count(), 0);
+        assert_eq!(list.splice(..1, []).next(), Some(0));
+        assert_eq!(list, vec![1, 20, 21, 8, 9, 10, 11, 12, 13, 14]);
+        assert!(list.chunks.iter().all(|chunk| !chunk.is_empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn drain_out_of_range() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        list.drain(5..11);
+    }
+
+    #[test]
+    fn extract_if() {
+        let mut list: ChunkList<i32, 2> = (0..10).collect();
+        let extracted: Vec<i32> = list.extract_if(|value| *value < 3 || *value % 3 == 0).collect();
+        assert_eq!(extracted, vec![0, 1, 2, 3, 6, 9]);
+        assert_eq!(list, vec![4, 5, 7, 8]);
+        assert_eq!(list.elements_count(), 4);
+
+        assert_eq!(list.extract_if(|value| *value > 4).next(), Some(5));
+        assert_eq!(list, vec![4, 7, 8]);
+    }
+
+    #[test]
+    fn split_off() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        let tail = list.split_off(4);
+        assert_eq!(list, vec![0, 1, 2, 3]);
+        assert_eq!(tail, vec![4, 5, 6, 7, 8, 9]);
+        assert_eq!(tail.elements_count(), 6);
+        assert_eq!(tail.chunks_count(), 3);
+
+        let tail = list.split_off(3);
+        assert_eq!(list, vec![0, 1, 2]);
+        assert_eq!(tail, vec![3]);
+        assert_eq!(list.split_off(3).elements_count(), 0);
+        assert_eq!(list.split_off(0), vec![0, 1, 2]);
+        assert_eq!(list.elements_count(), 0);
+    }
+
+    #[test]
+    fn split_off_keeps_configuration() {
+        use super::GrowingChunkPolicy;
+        static POLICY: GrowingChunkPolicy = GrowingChunkPolicy { initial_capacity: 2 };
+        let mut list: ChunkList<i32, 4> = (0..20).collect();
+        list.set_chunk_policy(&POLICY);
+        list.set_chunk_index(true);
+        list.set_min_fill_factor(0.25);
+        list.set_chunk_pool_limit(7);
+        let same_configuration = |other: &ChunkList<i32, 4>| {
+            std::ptr::addr_eq(other.chunk_policy(), &POLICY)
+                && other.has_chunk_index()
+                && other.min_fill_factor() == 0.25
+                && other.chunk_pool_limit == 7
+        };
+        let tail = list.split_off(6);
+        assert!(same_configuration(&tail));
+        assert_eq!(tail[0], 6);
+        tail.check_invariants().unwrap();
+        assert!(same_configuration(&list.split_off(6)));
+        let front = list.pop_front_n(3);
+        assert!(same_configuration(&front));
+        assert_eq!(front[2], 2);
+        front.check_invariants().unwrap();
+        assert!(same_configuration(&list.pop_back_n(1)));
+        let mut list: ChunkList<i32, 4> = (0..20).collect();
+        list.set_chunk_policy(&POLICY);
+        list.set_chunk_index(true);
+        list.set_min_fill_factor(0.25);
+        list.set_chunk_pool_limit(7);
+        for shard in list.split_into_shards(3) {
+            assert!(same_configuration(&shard));
+            shard.check_invariants().unwrap();
+        }
+    }
+
+    #[test]
+    fn append() {
+        let mut list: ChunkList<i32, 3> = (0..4).collect();
+        let mut other: ChunkList<i32, 3> = (4..9).collect();
+        other.pop_front();
+        other.pop_front();
+        list.append(&mut other);
+        assert_eq!(list, vec![0, 1, 2, 3, 6, 7, 8]);
+        assert_eq!(list.chunks_count(), 3);
+        assert_eq!(list.elements_count(), 7);
+        assert_eq!(other.elements_count(), 0);
+        assert_eq!(other.chunks_count(), 0);
+
+        let mut other: ChunkList<i32, 3> = (9..12).collect();
+        list.append(&mut other);
+        assert_eq!(list.chunks_count(), 4);
+        assert_eq!(list, (0..4).chain(6..12).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn truncate() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        list.truncate(5);
+        assert_eq!(list, vec![0, 1, 2, 3, 4]);
+        assert_eq!(list.chunks_count(), 2);
+        list.truncate(6);
+        assert_eq!(list.elements_count(), 5);
+        list.truncate_front(4);
+        assert_eq!(list, vec![1, 2, 3, 4]);
+        list.truncate_front(1);
+        assert_eq!(list, vec![4]);
+        assert_eq!(list.chunks_count(), 1);
+        list.truncate(0);
+        assert_eq!(list.chunks_count(), 0);
+    }
+
+    #[test]
+    fn resize() {
+        let mut list: ChunkList<i32, 3> = (0..2).collect();
+        list.resize(5, 7);
+        assert_eq!(list, vec![0, 1, 7, 7, 7]);
+        assert_eq!(list.chunks_count(), 2);
+        list.resize(1, 7);
+        assert_eq!(list, vec![0]);
+        let mut next = 0;
+        list.resize_with(4, || {
+            next += 1;
+            next
+        });
+        assert_eq!(list, vec![0, 1, 2, 3]);
+        assert_eq!(list.elements_count(), 4);
+    }
+
+    #[test]
+    fn swap() {
+        let mut list: ChunkList<String, 2> = (0..5).map(|value| value.to_string()).collect();
+        list.swap(0, 1);
+        list.swap(4, 2);
+        list.swap(3, 3);
+        assert_eq!(list, vec!["1", "0", "4", "3", "2"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn swap_out_of_bounds() {
+        let mut list: ChunkList<i32, 2> = (0..5).collect();
+        list.swap(1, 5);
+    }
+
+    #[test]
+    fn swap_remove() {
+        let mut list: ChunkList<i32, 2> = (0..5).collect();
+        assert_eq!(list.swap_remove_back(1), Some(1));
+        assert_eq!(list, vec![0, 4, 2, 3]);
+        assert_eq!(list.swap_remove_front(2), Some(2));
+        assert_eq!(list, vec![4, 0, 3]);
+        assert_eq!(list.swap_remove_back(2), Some(3));
+        assert_eq!(list.swap_remove_front(3), None);
+        assert_eq!(list, vec![4, 0]);
+        assert_eq!(list.elements_count(), 2);
+    }
+
+    #[test]
+    fn reverse() {
+        let mut list: ChunkList<i32, 3> = (0..7).collect();
+        list.reverse();
+        assert_eq!(list, vec![6, 5, 4, 3, 2, 1, 0]);
+        assert_eq!(list.chunks_count(), 3);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut list: ChunkList<i32, 3> = (0..7).collect();
+        list.rotate_left(2);
+        assert_eq!(list, vec![2, 3, 4, 5, 6, 0, 1]);
+        list.rotate_right(3);
+        assert_eq!(list, vec![6, 0, 1, 2, 3, 4, 5]);
+        list.rotate_left(7);
+        list.rotate_right(0);
+        assert_eq!(list, vec![6, 0, 1, 2, 3, 4, 5]);
+        assert_eq!(list.elements_count(), 7);
+    }
+
+    #[test]
+    fn dedup() {
+        let mut list: ChunkList<i32, 2> = [1, 1, 1, 1, 1, 2, 2, 3, 1, 1].into_iter().collect();
+        list.dedup();
+        assert_eq!(list, vec![1, 2, 3, 1]);
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list.chunks_count(), 4);
+
+        let mut list: ChunkList<i32, 3> = [10, 11, 20, 21, 22, 30].into_iter().collect();
+        list.dedup_by_key(|value| *value / 10);
+        assert_eq!(list, vec![10, 20, 30]);
+
+        let mut list: ChunkList<&str, 2> = ["a", "A", "b", "B", "c"].into_iter().collect();
+        list.dedup_by(|a, b| a.eq_ignore_ascii_case(b));
+        assert_eq!(list, vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn sort() {
+        let mut list: ChunkList<i32, 3> = [5, 3, 9, 1, 1, 8, 0, 7, 2, 6, 4].into_iter().collect();
+        list.sort();
+        assert_eq!(list, vec![0, 1, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(list.chunks_count(), 4);
+        list.sort_by(|a, b| b.cmp(a));
+        assert_eq!(list.front(), Some(&9));
+
+        let mut list: ChunkList<(i32, i32), 2> = [(2, 0), (1, 1), (2, 2), (1, 3), (0, 4)].into_iter().collect();
+        list.sort_by_key(|pair| pair.0);
+        assert_eq!(list, vec![(0, 4), (1, 1), (1, 3), (2, 0), (2, 2)]);
+    }
+
+    #[test]
+    fn sort_unstable() {
+        let mut list: ChunkList<i32, 3> = [5, 3, 9, 1, 1, 8, 0, 7, 2, 6, 4].into_iter().collect();
+        list.remove(1);
+        list.remove(5);
+        list.sort_unstable();
+        assert_eq!(list, vec![1, 1, 2, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(list.chunks_count(), 3);
+        list.sort_unstable_by_key(|value| -value);
+        assert_eq!(list, vec![9, 8, 7, 6, 5, 4, 2, 1, 1]);
+
+        let mut list: ChunkList<u32, 7> = (0..1000u32).map(|value| (value * 7919) % 503).collect();
+        let mut expected: Vec<u32> = list.iter().copied().collect();
+        expected.sort_unstable();
+        list.sort_unstable();
+        assert_eq!(list, expected);
+
+        let mut list: ChunkList<u32, 4> = (0..100).map(|_| 1).collect();
+        list.sort_unstable();
+        assert_eq!(list.elements_count(), 100);
+
+        let mut list: ChunkList<u32, 5> = (0..100u32).map(|value| (value * 31) % 17).collect();
+        let mut expected: Vec<u32> = list.iter().copied().collect();
+        expected.sort_unstable();
+        super::Packed { chunks: &mut list.chunks, chunk_size: 5 }.heapsort(0, 100, &mut u32::cmp);
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn sort_panicking_comparator() {
+        use std::panic::{catch_unwind, AssertUnwindSafe};
+        for unstable in [false, true] {
+            let mut list: ChunkList<i32, 3> = (0..20).rev().collect();
+            let mut calls = 0;
+            let compare = |a: &i32, b: &i32| {
+                calls += 1;
+                if calls == 30 {
+                    panic!("comparator panicked")
+                }
+                a.cmp(b)
+            };
+            let result = catch_unwind(AssertUnwindSafe(|| match unstable {
+                false => list.sort_by(compare),
+                true => list.sort_unstable_by(compare),
+            }));
+            assert!(result.is_err());
+            assert_eq!(list.elements_count(), list.iter().count());
+            assert_eq!(list.check_invariants(), Ok(()));
+            list.push_back(100);
+            assert_eq!(list.back(), Some(&100));
+        }
+    }
+
+    #[test]
+    fn binary_search() {
+        let mut list: ChunkList<i32, 3> = [1, 3, 3, 5, 7, 9, 11, 13].into_iter().collect();
+        list.remove(1);
+        assert_eq!(list.binary_search(&1), Ok(0));
+        assert_eq!(list.binary_search(&3), Ok(1));
+        assert_eq!(list.binary_search(&9), Ok(4));
+        assert_eq!(list.binary_search(&0), Err(0));
+        assert_eq!(list.binary_search(&4), Err(2));
+        assert_eq!(list.binary_search(&6), Err(3));
+        assert_eq!(list.binary_search(&14), Err(7));
+        assert_eq!(list.binary_search_by_key(&22, |value| value * 2), Ok(5));
+        assert_eq!(ChunkList::<i32, 3>::new().binary_search(&1), Err(0));
+    }
+
+    #[test]
+    fn binary_search_empty_end_chunks() {
+        let mut list: ChunkList<i32, 3> = [1, 3, 5, 7].into_iter().collect();
+        list.add_new_chunk_back();
+        list.add_new_chunk_front();
+        list.check_invariants().unwrap();
+        assert_eq!(list.binary_search(&1), Ok(0));
+        assert_eq!(list.binary_search(&7), Ok(3));
+        assert_eq!(list.binary_search(&0), Err(0));
+        assert_eq!(list.binary_search(&6), Err(3));
+        assert_eq!(list.binary_search(&8), Err(4));
+        let mut empty = ChunkList::<i32, 3>::new();
+        empty.add_new_chunk_back();
+        assert_eq!(empty.binary_search(&1), Err(0));
+    }
+
+    #[test]
+    fn bounds() {
+        let list: ChunkList<i32, 3> = [1, 3, 3, 3, 3, 5, 7].into_iter().collect();
+        assert_eq!(list.lower_bound(&3), 1);
+        assert_eq!(list.upper_bound(&3), 5);
+        assert_eq!(list.lower_bound(&0), 0);
+        assert_eq!(list.upper_bound(&7), 7);
+        assert_eq!(list.lower_bound(&4), 5);
+        assert_eq!(list.partition_point(|value| *value < 6), 6);
+    }
+
+    #[test]
+    fn bounds_empty_end_chunks() {
+        let mut list: ChunkList<i32, 3> = [1, 3, 3, 3, 3, 5, 7].into_iter().collect();
+        list.add_new_chunk_back();
+        list.add_new_chunk_front();
+        assert_eq!(list.lower_bound(&3), 1);
+        assert_eq!(list.upper_bound(&3), 5);
+        assert_eq!(list.lower_bound(&0), 0);
+        assert_eq!(list.upper_bound(&7), 7);
+        assert_eq!(list.partition_point(|value| *value < 6), 6);
+        let mut empty = ChunkList::<i32, 3>::new();
+        empty.add_new_chunk_front();
+        assert_eq!(empty.lower_bound(&1), 0);
+    }
+
+    #[test]
+    fn insert_sorted() {
+        let mut list = ChunkList::<i32, 2>::new();
+        for value in [5, 1, 4, 2, 3, 0, 2] {
+            list.insert_sorted(value);
+        }
+        assert_eq!(list, vec![0, 1, 2, 2, 3, 4, 5]);
+
+        let mut list = ChunkList::<(i32, char), 2>::new();
+        assert_eq!(list.insert_sorted_by_key((1, 'a'), |pair| pair.0), 0);
+        assert_eq!(list.insert_sorted_by_key((0, 'b'), |pair| pair.0), 0);
+        assert_eq!(list.insert_sorted_by_key((1, 'c'), |pair| pair.0), 2);
+        assert_eq!(list, vec![(0, 'b'), (1, 'a'), (1, 'c')]);
+    }
+
+    #[test]
+    fn is_sorted() {
+        let mut list: ChunkList<i32, 2> = (0..5).collect();
+        assert!(list.is_sorted());
+        list.push_back(3);
+        assert!(!list.is_sorted());
+        list.swap(1, 2);
+        list.pop_back();
+        assert!(!list.is_sorted());
+        assert!(list.is_sorted_by_key(|value| *value / 3));
+        assert!(ChunkList::<i32, 2>::new().is_sorted());
+    }
+
+    #[test]
+    fn merge() {
+        let list: ChunkList<i32, 3> = [1, 3, 5, 7].into_iter().collect();
+        let other: ChunkList<i32, 3> = [0, 2, 3, 8, 9].into_iter().collect();
+        let merged = list.clone().merge(other.clone());
+        assert_eq!(merged, vec![0, 1, 2, 3, 3, 5, 7, 8, 9]);
+        assert_eq!(merged.chunks_count(), 3);
+
+        let mut iter = list.merge_iter_by(other, |a, b| a.cmp(b));
+        assert_eq!(iter.len(), 9);
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.len(), 8);
+
+        let list: ChunkList<(i32, char), 2> = [(1, 'a'), (2, 'a')].into_iter().collect();
+        let other: ChunkList<(i32, char), 2> = [(1, 'b'), (2, 'b')].into_iter().collect();
+        let merged = list.merge_by(other, |a, b| a.0.cmp(&b.0));
+        assert_eq!(merged, vec![(1, 'a'), (1, 'b'), (2, 'a'), (2, 'b')]);
+    }
+
+    #[test]
+    fn cursor() {
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let mut cursor = list.cursor_front();
+        assert_eq!(cursor.current(), Some(&0));
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.index(), Some(2));
+        assert_eq!(cursor.peek_prev(), Some(&1));
+        assert_eq!(cursor.peek_next(), Some(&3));
+        cursor.move_next();
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(cursor.current(), None);
+        assert_eq!(cursor.peek_next(), Some(&0));
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&4));
+        let mut cursor = list.cursor_front();
+        cursor.move_prev();
+        assert_eq!(cursor.index(), None);
+        assert_eq!(ChunkList::<i32, 2>::new().cursor_back().current(), None);
+    }
+
+    #[test]
+    fn cursor_mut() {
+        let mut list: ChunkList<i32, 2> = (0..4).collect();
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        cursor.insert_before(10);
+        cursor.insert_after(11);
+        assert_eq!(cursor.current(), Some(&mut 1));
+        assert_eq!(cursor.index(), Some(2));
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(11));
+        assert_eq!(cursor.current(), Some(&mut 2));
+        *cursor.current().unwrap() = 20;
+        cursor.splice_before((30..32).collect());
+        assert_eq!(cursor.index(), Some(5));
+        cursor.splice_after((40..42).collect());
+        assert_eq!(cursor.current(), Some(&mut 20));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 40));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.current(), Some(&mut 31));
+        assert_eq!(list, vec![0, 10, 1, 30, 31, 20, 40, 41, 3]);
+        assert_eq!(list.elements_count(), 9);
+
+        let mut cursor = list.cursor_back_mut();
+        cursor.move_next();
+        cursor.insert_after(-1);
+        cursor.insert_before(100);
+        assert_eq!(cursor.remove_current(), None);
+        cursor.move_next();
+        assert_eq!(cursor.remove_current(), Some(-1));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.remove_current(), Some(100));
+        assert_eq!(cursor.index(), None);
+        assert_eq!(list, vec![0, 10, 1, 30, 31, 20, 40, 41, 3]);
+
+        let mut list: ChunkList<i32, 1> = (0..3).collect();
+        list.set_chunk_index(true);
+        let mut cursor = list.cursor_front_mut();
+        cursor.insert_after(10);
+        assert_eq!(cursor.current(), Some(&mut 0));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&mut 10));
+        cursor.insert_after(11);
+        cursor.insert_before(9);
+        assert_eq!(cursor.current(), Some(&mut 10));
+        assert_eq!(cursor.index(), Some(2));
+        let mut cursor = list.cursor_back_mut();
+        cursor.insert_after(3);
+        assert_eq!(cursor.current(), Some(&mut 2));
+        assert_eq!(list, vec![0, 9, 10, 11, 1, 2, 3]);
+        assert_eq!(list.elements_count(), 7);
+        assert_eq!(list.get(4), Some(&1));
+    }
+
+    #[test]
+    fn handles() {
+        use super::HandleChunkList;
+
+        let mut list = HandleChunkList::<&str, 2>::new();
+        let b = list.push_back("b");
+        let a = list.push_front("a");
+        let c = list.push_back("c");
+        assert_eq!(list.get_by_handle(b), Some(&"b"));
+        assert_eq!(list.remove_by_handle(a), Some("a"));
+        assert_eq!(list.remove_by_handle(a), None);
+        assert_eq!(list.get_by_handle(a), None);
+        let d = list.push_front("d");
+        assert_ne!(a, d);
+        assert_eq!(list.get_by_handle(a), None);
+        *list.get_by_handle_mut(c).unwrap() = "C";
+        assert_eq!(list.iter().map(|(_, value)| *value).collect::<Vec<_>>(), vec!["d", "b", "C"]);
+        assert_eq!(list.len(), 3);
+        assert_eq!(list.pop_back(), Some("C"));
+        assert!(!list.contains(c));
+        assert!(list.contains(b));
+        list.remove_by_handle(b);
+        assert_eq!(list.iter().rev().map(|(id, _)| id).collect::<Vec<_>>(), vec![d]);
+
+
+        let mut list = HandleChunkList::<i32, 2>::new();
+        let ids: Vec<_> = (0..100).map(|value| list.push_back(value)).collect();
+        for id in &ids[..90] {
+            list.remove_by_handle(*id);
+        }
+        assert!(list.order.elements_count() < 30);
+        assert_eq!(list.pop_front(), Some(90));
+        assert_eq!(list.len(), 9);
+
+        let mut list = HandleChunkList::<i32, 2>::new();
+        let old = list.push_back(1);
+        list.clear();
+        let new = list.push_back(2);
+        assert_ne!(old, new);
+        assert_eq!(list.get_by_handle(old), None);
+        assert!(!list.contains(old));
+        assert_eq!(list.get_by_handle(new), Some(&2));
+    }
+
+    #[test]
+    fn hash() {
+        use std::collections::HashSet;
+
+        let list: ChunkList<i32, 2> = (0..5).collect();
+        let mut other = ChunkList::<i32, 2>::new();
+        for value in (0..5).rev() {
+            other.push_front(value);
+        }
+        let mut set = HashSet::new();
+        set.insert(list);
+        assert!(set.contains(&other));
+        other.pop_back();
+        assert!(!set.contains(&other));
+    }
+
+    #[test]
+    fn range() {
+        let mut list: ChunkList<i32, 3> = (0..10).collect();
+        assert_eq!(list.range(2..8).copied().collect::<Vec<_>>(), vec![2, 3, 4, 5, 6, 7]);
+        assert_eq!(list.range(4..=5).rev().copied().collect::<Vec<_>>(), vec![5, 4]);
+        assert_eq!(list.range(3..6).len(), 3);
+        assert_eq!(list.range(5..5).next(), None);
+        assert_eq!(list.range(..).count(), 10);
+        for value in list.range_mut(1..4) {
+            *value *= 10;
+        }
+        for value in list.range_mut(7..8) {
+            *value = -1;
+        }
+        assert_eq!(list.range_mut(9..9).next(), None);
+        assert_eq!(list, vec![0, 10, 20, 30, 4, 5, 6, -1, 8, 9]);
+    }
+
+    #[test]
+    fn chunks() {
+        let mut list: ChunkList<i32, 3> = (0..7).collect();
+        let chunks: Vec<&[i32]> = list.chunks().collect();
+        assert_eq!(chunks, vec![&[0, 1, 2][..], &[3, 4, 5], &[6]]);
+        assert_eq!(list.chunks().len(), 3);
+        for chunk in list.chunks_mut().rev() {
+            chunk.reverse();
+        }
+        assert_eq!(list, vec![2, 1, 0, 5, 4, 3, 6]);
+    }
+
+    #[test]
+    fn into_vec() {
+        let mut list: ChunkList<i32, 3> = (0..8).collect();
+        list.remove(1);
+        assert_eq!(list.clone().into_vec(), vec![0, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(ChunkList::<i32, 3>::new().into_vec(), Vec::<i32>::new());
+        assert_eq!(list.make_contiguous(), None);
+
+        let mut list: ChunkList<i32, 4> = (0..6).collect();
+        list.remove(0);
+        list.remove(0);
+        list.remove(3);
+        assert_eq!(list.chunks_count(), 2);
+        assert_eq!(list.make_contiguous(), Some(&mut [2, 3, 4][..]));
+        assert_eq!(list.chunks_count(), 1);
+        assert_eq!(ChunkList::<i32, 4>::new().make_contiguous(), Some(&mut [][..]));
+    }
+
+    #[test]
+    fn into_chunks() {
+        let list: ChunkList<String, 2> = (0..3).map(|value| value.to_string()).collect();
+        let mut chunks = list.into_chunks();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks.next(), Some(vec!["0".to_string(), "1".to_string()]));
+        assert_eq!(chunks.next(), Some(vec!["2".to_string()]));
+        assert_eq!(chunks.next(), None);
+    }
+
+    #[test]
+    fn from() {
+        use std::collections::{LinkedList, VecDeque};
+
+        let expected: Vec<i32> = (0..7).collect();
+        let list = ChunkList::<i32, 3>::from(expected.clone());
+        assert_eq!(list, expected);
+        assert_eq!(list.chunks_count(), 3);
+        assert_eq!(ChunkList::<i32, 3>::from(&expected[..]), expected);
+        assert_eq!(ChunkList::<i32, 3>::from(&expected[..]).elements_count(), 7);
+        assert_eq!(ChunkList::<i32, 3>::from([0, 1, 2, 3, 4, 5, 6]), expected);
+        assert_eq!(ChunkList::<i32, 3>::from(VecDeque::from(expected.clone())), expected);
+        assert_eq!(ChunkList::<i32, 3>::from(expected.iter().copied().collect::<LinkedList<_>>()), expected);
+        assert_eq!(ChunkList::<i32, 3>::from(Vec::new()).chunks_count(), 0);
+    }
+
+    #[test]
+    fn into_std() {
+        use std::collections::VecDeque;
+
+        let list: ChunkList<i32, 3> = (0..7).collect();
+        let vec: Vec<i32> = list.clone().into();
+        assert_eq!(vec, (0..7).collect::<Vec<_>>());
+        let deque: VecDeque<i32> = list.into();
+        assert_eq!(deque, (0..7).collect::<VecDeque<_>>());
+    }
+
+    #[test]
+    fn chunk_list_macro() {
+        let list: ChunkList<i32, 2> = chunk_list![1, 2, 3];
+        assert_eq!(list, vec![1, 2, 3]);
+        let list: ChunkList<i32, 4> = chunk_list![7; 10];
+        assert_eq!(list, vec![7; 10]);
+        assert_eq!(list.chunks_count(), 3);
+        let list = chunk_list![size = 3; 1, 2, 3, 4,];
+        assert_eq!(list.chunks_count(), 2);
+        let list = chunk_list![size = 3; String::new(); 4];
+        assert_eq!(list.elements_count(), 4);
+        let list: ChunkList<i32, 2> = chunk_list![];
+        assert_eq!(list.elements_count(), 0);
+    }
+
+    #[test]
+    fn extend_from_slice() {
+        let mut list: ChunkList<u8, 4> = chunk_list![1, 2];
+        list.extend_from_slice(b"abcdefg");
+        assert_eq!(list, vec![1, 2, b'a', b'b', b'c', b'd', b'e', b'f', b'g']);
+        assert_eq!(list.chunks_count(), 3);
+        assert_eq!(list.elements_count(), 9);
+        list.extend_from_slice(&[]);
+        assert_eq!(list.chunks_count(), 3);
+
+        let mut list: ChunkList<u8, 4> = chunk_list![1, 2];
+        list.pop_front();
+        list.extend_from_copy_slice(b"abcdefg");
+        assert_eq!(list, vec![2, b'a', b'b', b'c', b'd', b'e', b'f', b'g']);
+        assert_eq!(list.chunks_count(), 2);
+        list.check_invariants().unwrap();
+    }
+
+    #[test]
+    fn reserve() {
+        let mut list = ChunkList::<i32, 4>::with_capacity(10);
+        assert_eq!(list.spare_chunks.len(), 3);
+        assert!(list.chunks.capacity() >= 3);
+        list.extend(0..6);
+        assert_eq!(list.spare_chunks.len(), 1);
+        list.reserve(6);
+        assert_eq!(list.spare_chunks.len(), 1);
+        list.reserve(7);
+        assert_eq!(list.spare_chunks.len(), 2);
+        list.push_front(-1);
+        list.insert(3, 10);
+        assert_eq!(list.spare_chunks.len(), 0);
+        assert_eq!(list, vec![-1, 0, 1, 10, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn capacity() {
+        let mut list = ChunkList::<i32, 4>::new();
+        assert_eq!(list.capacity(), 0);
+        assert_eq!(list.chunk_capacity(), 4);
+        list.push_back(1);
+        assert_eq!(list.capacity(), 4);
+        assert_eq!(list.spare_capacity(), 3);
+        list.reserve(10);
+        assert_eq!(list.capacity(), 12);
+        assert_eq!(list.spare_capacity(), 11);
+    }
+
+    #[test]
+    fn chunk_pool() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.set_chunk_pool_limit(2);
+        list.extend(0..8);
+        assert_eq!(list.capacity(), 8);
+        list.truncate(1);
+        assert_eq!(list.spare_chunks.len(), 2);
+        assert_eq!(list.capacity(), 6);
+        list.extend(1..6);
+        assert_eq!(list.spare_chunks.len(), 0);
+        list.retain(|value| *value >= 4);
+        list.pop_front();
+        assert_eq!(list.spare_chunks.len(), 2);
+        list.clear();
+        assert_eq!(list.spare_chunks.len(), 2);
+        list.set_chunk_pool_limit(0);
+        assert_eq!(list.capacity(), 0);
+        assert_eq!(list.chunk_pool_limit(), 0);
+        list.push_back(1);
+        list.pop_back();
+        assert_eq!(list.capacity(), 0);
+    }
+
+    #[test]
+    fn shrink_to_fit() {
+        let mut list = ChunkList::<i32, 4>::with_capacity(100);
+        list.extend(0..10);
+        list.drain(1..9);
+        list.shrink_to_fit();
+        assert_eq!(list.spare_chunks.len(), 0);
+        assert!(list.chunks.capacity() < 25);
+        assert!(list.chunks.iter().all(|chunk| chunk.elements.capacity() < 4));
+        list.push_back(10);
+        assert_eq!(list, vec![0, 9, 10]);
+    }
+
+    #[test]
+    fn iter_mut() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_back(1);
+        list.push_back(2);
+        list.push_back(3);
+
+        for value in list.iter_mut() {
+            *value *= 10;
+        }
+        let mut iter = list.iter();
+        assert_eq!(iter.next(), Some(&10));
+        assert_eq!(iter.next(), Some(&20));
+        assert_eq!(iter.next(), Some(&30));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn erase() {
+        let mut list = ChunkList::<i32, 2>::new();
+        list.push_front(1);
+        list.push_front(2);
+        list.push_front(3);
+        list.clear();
+        assert_eq!(list.get(0), None);
+    }
+
+    #[test]
+    fn elements_count() {
+        let mut list = ChunkList::<i32, 2>::new();
+        assert_eq!(list.elements_count(), 0);
         list.push_front(1);
         assert_eq!(list.elements_count(), 1);
         list.push_front(2);
@@ -339,9 +6882,1171 @@ mod test {
         list.pop_back();
         assert_eq!(list.elements_count(), 0);
     }
-}
 
+    #[test]
+    fn chunk_ring_buffer() {
+        let mut chunk = super::Chunk::<i32, super::Const<4>>::new();
+        assert!(chunk.push_back(2));
+        assert!(chunk.push_back(3));
+        assert!(chunk.push_front(1));
+        assert!(chunk.push_front(0));
+        assert!(!chunk.push_front(-1));
+        assert_eq!(chunk.as_slice(), &[0, 1, 2, 3]);
+        assert_eq!(chunk.pop_front(), Some(0));
+        assert_eq!(chunk.pop_front(), Some(1));
+        assert!(chunk.push_back(4));
+        assert!(chunk.push_back(5));
+        assert_eq!(chunk.as_slice(), &[2, 3, 4, 5]);
+        assert!(!chunk.insert(0, 1));
+        assert_eq!(chunk.pop_back(), Some(5));
+        assert!(chunk.insert(0, 1));
+        assert_eq!(chunk.as_mut_slice(), &mut [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn chunk_ring_buffer_amortised() {
+        // Used as a queue, a nearly full chunk is rebuilt rarely instead of on every other push.
+        const N: usize = 64;
+        let mut chunk = super::Chunk::<usize, super::Const<N>>::new();
+        for value in 0..N - 1 {
+            chunk.push_back(value);
+        }
+        let mut moves = 0;
+        for value in N - 1..N + 1000 {
+            let second = chunk.as_slice()[1..].as_ptr();
+            chunk.pop_front();
+            assert!(chunk.push_back(value));
+            moves += (chunk.as_slice().as_ptr() != second) as usize;
+            assert!(chunk.as_slice().iter().copied().eq(value + 2 - N..=value));
+        }
+        assert!(moves <= 1000 / (N / 6), "moved {} times", moves);
 
-fn main() {
+        // Alternating ends.
+        let mut chunk = super::Chunk::<usize, super::Const<N>>::new();
+        let mut moves = 0;
+        for value in 0..N {
+            let front = chunk.as_slice().as_ptr();
+            match value % 2 {
+                0 => chunk.push_back(value),
+                _ => chunk.push_front(value),
+            };
+            moves += (value % 2 == 0 && chunk.as_slice().as_ptr() != front) as usize;
+        }
+        assert!(chunk.is_full());
+        assert!(moves <= 8, "moved {} times", moves);
+    }
+
+    #[test]
+    fn const_new() {
+        static EMPTY: ChunkList<i32, 4> = ChunkList::new();
+        assert_eq!(EMPTY.elements_count(), 0);
+        assert_eq!(EMPTY.chunks_count(), 0);
+    }
+
+    #[test]
+    fn dyn_chunk_list() {
+        use super::DynChunkList;
+        let mut list = DynChunkList::new(3);
+        assert_eq!(list.chunk_capacity(), 3);
+        list.extend(1..=7);
+        list.push_front(0);
+        assert_eq!(list.chunks_count(), 4);
+        list.insert(2, 10);
+        assert_eq!(list.remove(3), Some(2));
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), vec![0, 1, 10, 3, 4, 5, 6, 7]);
+        assert_eq!(list.iter().next_back(), Some(&7));
+        list.retain(|value| value % 2 == 1);
+        for value in &mut list {
+            *value *= 10;
+        }
+        assert_eq!(list[1], 30);
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list.pop_back(), Some(70));
+        assert_eq!(list.pop_front(), Some(10));
+        let chunk_list: ChunkList<i32, 2> = list.clone().into();
+        assert_eq!(chunk_list, [30, 50]);
+        let converted = DynChunkList::from(chunk_list);
+        assert_eq!(converted.chunk_capacity(), 2);
+        assert_eq!(converted, list);
+        assert_eq!(list.into_iter().collect::<Vec<_>>(), vec![30, 50]);
+
+        let mut list = DynChunkList::with_capacity(4, 10);
+        list.set_chunk_index(true);
+        list.extend([5, 3, 9, 1, 7, 2]);
+        list.sort();
+        assert_eq!(list, [1, 2, 3, 5, 7, 9]);
+        assert_eq!(list, ChunkList::<i32, 3>::from([1, 2, 3, 5, 7, 9]));
+        assert_eq!(list.binary_search(&7), Ok(4));
+        assert_eq!(list.split_off(2).chunk_capacity(), 4);
+
+        let mut list = DynChunkList::new(2);
+        list.extend([1, 4]);
+        let mut other = DynChunkList::new(5);
+        other.extend([2, 3, 5]);
+        let merged = list.merge(other);
+        assert_eq!(merged.chunk_capacity(), 2);
+        assert!(merged.chunks().all(|chunk| chunk.len() <= 2));
+        assert_eq!(merged, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk sizes should be equal (are 2 and 3)")]
+    fn dyn_chunk_list_append_mismatched_chunk_sizes() {
+        let mut list = super::DynChunkList::new(2);
+        list.extend([1, 2, 3]);
+        let mut other = super::DynChunkList::new(3);
+        other.push_back(4);
+        list.append(&mut other);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk size should be > 0")]
+    fn dyn_chunk_list_zero_chunk_size() {
+        super::DynChunkList::<i32>::new(0);
+    }
+
+    #[test]
+    fn chunk_policy() {
+        use super::{ChunkPolicy, GrowingChunkPolicy};
+
+        struct AppendHeavy;
+        impl ChunkPolicy for AppendHeavy {
+            fn split_at(&self, capacity: usize) -> usize {
+                capacity
+            }
+
+            fn should_merge(&self, _left_len: usize, _right_len: usize, _capacity: usize) -> bool {
+                true
+            }
+        }
+
+        let mut list = ChunkList::<i32, 4>::from([0, 1, 2, 3]);
+        list.set_chunk_policy(&AppendHeavy);
+        list.insert(1, 10);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[0, 10, 1, 2][..], &[3]]);
+        list.remove(0);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[10, 1, 2, 3][..]]);
+
+        static GROWING: GrowingChunkPolicy = GrowingChunkPolicy { initial_capacity: 2 };
+        let mut list = ChunkList::<i32, 16>::new();
+        list.set_chunk_policy(&GROWING);
+        list.push_back(0);
+        assert_eq!(list.capacity(), 2);
+        list.extend(1..5);
+        assert_eq!(list.capacity(), 5);
+        list.extend(5..20);
+        assert_eq!(list.chunks().map(<[i32]>::len).collect::<Vec<_>>(), vec![16, 4]);
+        assert_eq!(list.capacity(), 20);
+        assert!(list.iter().copied().eq(0..20));
+    }
+
+    #[test]
+    fn min_fill_factor() {
+        let mut list = ChunkList::<i32, 4>::from_iter(0..16);
+        list.set_min_fill_factor(0.5);
+        assert_eq!(list.min_fill_factor(), 0.5);
+        list.remove(1);
+        list.remove(1);
+        list.remove(1);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[0, 4][..], &[5, 6, 7], &[8, 9, 10, 11], &[12, 13, 14, 15]]);
+        list.drain(3..11);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[0, 4][..], &[5, 14, 15]]);
+        list.retain(|value| value % 3 != 0);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[4, 5, 14][..]]);
+        let mut list = ChunkList::<i32, 4>::from_iter(0..16);
+        list.set_min_fill_factor(0.5);
+        list.retain(|value| value % 4 == 0);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[0, 4][..], &[8, 12]]);
+    }
+
+    #[test]
+    #[should_panic(expected = "fill factor")]
+    fn min_fill_factor_out_of_range() {
+        ChunkList::<i32, 4>::new().set_min_fill_factor(0.75);
+    }
+
+    #[test]
+    fn defragment() {
+        let mut list = ChunkList::<i32, 3>::from_iter(0..12);
+        list.retain(|value| value % 2 == 0);
+        assert_eq!(list.chunks_count(), 4);
+        assert_eq!(list.defragment(), 2);
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[0, 2, 4][..], &[6, 8, 10]]);
+        assert_eq!(list.defragment(), 0);
+        assert_eq!(ChunkList::<i32, 3>::new().defragment(), 0);
+    }
+
+    #[test]
+    fn occupancy_stats() {
+        let mut list = ChunkList::<i32, 4>::from_iter(0..12);
+        assert_eq!(list.fill_factor(), 1.0);
+        list.retain(|value| value % 4 != 0 && *value != 7 && *value < 10);
+        assert_eq!(list.fill_factor(), 0.5);
+        let stats = list.occupancy_stats().unwrap();
+        assert_eq!((stats.min, stats.max, stats.average), (1, 3, 2.0));
+        assert_eq!(stats.histogram, vec![0, 1, 1, 1, 0]);
+        assert_eq!(ChunkList::<i32, 4>::new().occupancy_stats(), None);
+        assert_eq!(ChunkList::<i32, 4>::new().fill_factor(), 1.0);
+    }
+
+    #[test]
+    fn heap_size() {
+        use std::mem::size_of;
+        let mut list = ChunkList::<u64, 4>::new();
+        assert_eq!(list.heap_size_bytes(), 0);
+        list.extend(0..6);
+        let heap_size = list.heap_size();
+        // Two chunks of four u64.
+        assert_eq!(heap_size.chunk_buffers, 64);
+        assert_eq!(heap_size.wasted, 16);
+        assert!(heap_size.spine >= 2 * size_of::<super::Chunk<u64, super::Const<4>>>());
+        assert_eq!(list.heap_size_bytes(), heap_size.spine + heap_size.chunk_buffers);
+    }
+
+    #[test]
+    fn cursor_seek() {
+        let mut list = ChunkList::<i32, 3>::from_iter(0..10);
+        list.remove(4);
+        let mut cursor = list.cursor_front();
+        for i in [0, 1, 5, 2, 8, 7, 3] {
+            cursor.seek(i);
+            assert_eq!(cursor.index(), Some(i));
+            assert_eq!(cursor.current(), list.get(i));
+        }
+        cursor.seek(9);
+        assert_eq!(cursor.index(), None);
+        cursor.seek(6);
+        assert_eq!(cursor.current(), Some(&7));
+        cursor.move_next();
+        assert_eq!(cursor.current(), Some(&8));
+        let mut cursor = list.cursor_back_mut();
+        cursor.seek(3);
+        *cursor.current().unwrap() *= 10;
+        assert_eq!(list.range(2..5).copied().collect::<Vec<_>>(), vec![2, 30, 5]);
+    }
+
+    #[test]
+    fn chunk_index() {
+        let mut list = ChunkList::<i32, 4>::from_iter(0..20);
+        list.set_chunk_index(true);
+        assert!(list.has_chunk_index());
+        list.push_back(20);
+        list.insert(5, 100);
+        assert_eq!(list.remove(10), Some(9));
+        assert_eq!(list.pop_back(), Some(20));
+        list.push_front(-1);
+        assert_eq!(list[6], 100);
+        list[7] = 50;
+        list.retain(|value| value % 3 != 0);
+        list.extend_from_slice(&[30, 31]);
+        let expected = [-1, 1, 2, 4, 100, 50, 7, 8, 10, 11, 13, 14, 16, 17, 19, 30, 31];
+        for (i, value) in expected.iter().enumerate() {
+            assert_eq!(list.get(i), Some(value));
+        }
+        assert_eq!(list.get(expected.len()), None);
+        assert_eq!(list.check_invariants(), Ok(()));
+        list.set_chunk_index(false);
+        assert!(!list.has_chunk_index());
+        assert_eq!(list, expected);
+    }
+
+    #[test]
+    fn iter_both_ends() {
+        let list = ChunkList::<i32, 3>::from_iter(0..10);
+        let mut iter = list.iter();
+        assert_eq!(iter.len(), 10);
+        assert_eq!(iter.next(), Some(&0));
+        assert_eq!(iter.next_back(), Some(&9));
+        let rest = iter.clone();
+        assert_eq!(iter.by_ref().rev().take(5).copied().collect::<Vec<_>>(), vec![8, 7, 6, 5, 4]);
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(rest.len(), 8);
+        let mut range = list.range(2..4);
+        assert_eq!((range.next_back(), range.next_back(), range.next_back()), (Some(&3), Some(&2), None));
+        assert_eq!(range.next(), None);
+    }
+
+    #[test]
+    fn iter_nth() {
+        let mut list = ChunkList::<i32, 4>::from_iter(0..30);
+        for n in 0..32 {
+            assert_eq!(list.iter().nth(n), (0..30).nth(n).as_ref());
+            assert_eq!(list.iter().skip(n).copied().collect::<Vec<_>>(), (0..30).skip(n).collect::<Vec<_>>());
+            assert_eq!(list.clone().into_iter().nth(n), (0..30).nth(n));
+        }
+        let mut iter = list.iter();
+        assert_eq!(iter.next_back(), Some(&29));
+        assert_eq!(iter.nth(9), Some(&9));
+        assert_eq!(iter.len(), 19);
+        assert_eq!(iter.nth(17), Some(&27));
+        assert_eq!(iter.nth(1), None);
+        assert_eq!(iter.len(), 0);
+        assert_eq!(list.range(5..20).nth(3), Some(&8));
+        if let Some(value) = list.iter_mut().nth(13) {
+            *value = -1;
+        }
+        assert_eq!(list.iter_mut().nth(13), Some(&mut -1));
+        let mut into_iter = list.into_iter();
+        assert_eq!(into_iter.nth(6), Some(6));
+        assert_eq!(into_iter.len(), 23);
+        assert_eq!(into_iter.nth(30), None);
+        assert_eq!(into_iter.len(), 0);
+    }
+
+    #[test]
+    fn try_reserve() {
+        use super::GrowingChunkPolicy;
+        let mut list = ChunkList::<i32, 4>::from_iter(0..5);
+        list.try_reserve(10).unwrap();
+        assert!(list.spare_capacity() >= 10);
+        let capacity = list.capacity();
+        for value in 5..15 {
+            list.try_push_back(value).unwrap();
+        }
+        assert_eq!(list.capacity(), capacity);
+        assert!(list.iter().copied().eq(0..15));
+        assert!(list.try_reserve(usize::MAX).is_err());
+        assert!(list.iter().copied().eq(0..15));
+
+        // The back chunk wraps and gets recentered on the fallible path.
+        let mut list = ChunkList::<i32, 4>::from_iter(0..3);
+        list.set_chunk_index(true);
+        list.pop_front();
+        for value in 3..7 {
+            list.try_push_back(value).unwrap();
+        }
+        assert_eq!(list.chunks().collect::<Vec<_>>(), vec![&[1, 2, 3, 4][..], &[5, 6]]);
+        assert_eq!(list.get(5), Some(&6));
+
+        static GROWING: GrowingChunkPolicy = GrowingChunkPolicy { initial_capacity: 1 };
+        let mut list = ChunkList::<i32, 8>::new();
+        list.set_chunk_policy(&GROWING);
+        list.push_back(0);
+        list.try_reserve(7).unwrap();
+        assert_eq!(list.capacity(), 8);
+    }
+
+    #[test]
+    fn try_methods() {
+        use super::{Chunk, ChunkListError, Const, DynChunkList};
+        let mut list = ChunkList::<i32, 3>::from_iter(0..5);
+        assert_eq!(list.try_get(4), Ok(&4));
+        assert_eq!(list.try_get(5), Err(ChunkListError::IndexOutOfBounds { index: 5, len: 5 }));
+        *list.try_get_mut(0).unwrap() = 10;
+        assert_eq!(list.try_insert(6, 6), Err(ChunkListError::IndexOutOfBounds { index: 6, len: 5 }));
+        assert_eq!(list.try_insert(5, 5), Ok(()));
+        assert_eq!(list.try_remove(0), Ok(10));
+        assert_eq!(list.try_remove(5), Err(ChunkListError::IndexOutOfBounds { index: 5, len: 5 }));
+        assert!(list.iter().copied().eq(1..6));
+        assert_eq!(
+            ChunkListError::IndexOutOfBounds { index: 5, len: 5 }.to_string(),
+            "index out of bounds: the len is 5 but the index is 5",
+        );
+
+        let mut chunk = Chunk::<i32, Const<2>>::new();
+        assert_eq!(chunk.try_get(7), Err(ChunkListError::IndexOutOfBounds { index: 7, len: 0 }));
+        assert_eq!(chunk.try_insert(1, 1), Err(ChunkListError::IndexOutOfBounds { index: 1, len: 0 }));
+        assert_eq!(chunk.try_insert(0, 1), Ok(()));
+        assert_eq!(chunk.try_insert(0, 0), Ok(()));
+        assert_eq!(chunk.try_insert(0, 2), Err(ChunkListError::ChunkFull));
+        *chunk.try_get_mut(1).unwrap() = 3;
+        assert_eq!(chunk.try_remove(1), Ok(3));
+        assert_eq!(chunk.try_remove(1), Err(ChunkListError::IndexOutOfBounds { index: 1, len: 1 }));
+
+        assert_eq!(DynChunkList::<i32>::try_new(0).err(), Some(ChunkListError::ZeroChunkSize));
+        assert_eq!(DynChunkList::<i32>::try_new(4).unwrap().chunk_capacity(), 4);
+    }
+
+    #[test]
+    fn check_invariants() {
+        use super::{Chunk, InvariantViolation};
+        let mut list = ChunkList::<i32, 3>::from_iter(0..10);
+        list.set_chunk_index(true);
+        list.insert(4, 4);
+        list.remove(0);
+        list.retain(|value| value % 3 != 0);
+        list.extend(0..5);
+        assert_eq!(list.check_invariants(), Ok(()));
+
+        list.elements_count += 1;
+        list.chunks.insert(1, Chunk::new());
+        list.chunks[2].elements.extend([7, 7, 7]);
+        list.spare_chunks.push(Chunk::new());
+        list.spare_chunks[0].push_back(1);
+        let violations = list.check_invariants().unwrap_err();
+        assert!(violations.contains(&InvariantViolation::EmptyChunk { chunk_i: 1 }));
+        assert!(violations.iter().any(|violation| matches!(violation, InvariantViolation::ChunkOverflow { chunk_i: 2, .. })));
+        assert!(violations.iter().any(|violation| matches!(violation, InvariantViolation::ChunkIndex { .. })));
+        assert!(violations.iter().any(|violation| matches!(violation, InvariantViolation::ElementsCount { .. })));
+        assert!(violations.contains(&InvariantViolation::NonEmptySpareChunk { spare_i: 0, len: 1 }));
+        assert_eq!(InvariantViolation::EmptyChunk { chunk_i: 1 }.to_string(), "interior chunk 1 is empty");
+    }
+
+    #[test]
+    fn split_into_shards() {
+        let list = ChunkList::<i32, 4>::from_iter(0..50);
+        let shards = list.clone().split_into_shards(3);
+        assert_eq!(shards.iter().map(ChunkList::elements_count).collect::<Vec<_>>(), vec![16, 16, 18]);
+        assert!(shards.iter().all(|shard| shard.check_invariants().is_ok()));
+        let joined = ChunkList::from_shards(shards);
+        assert_eq!(joined, list);
+        assert_eq!(joined.chunks_count(), list.chunks_count());
+
+        let shards = list.clone().split_into_shards(20);
+        assert_eq!(shards.len(), 20);
+        assert_eq!(shards.iter().filter(|shard| shard.elements_count() > 0).count(), 13);
+        assert_eq!(ChunkList::from_shards(shards), list);
+        assert!(ChunkList::<i32, 4>::new().split_into_shards(2).iter().all(|shard| shard.elements_count() == 0));
+
+        let mut list = list;
+        list.set_chunk_index(true);
+        list.set_min_fill_factor(0.5);
+        list.set_chunk_pool_limit(1);
+        let joined = ChunkList::from_shards(list.clone().split_into_shards(3));
+        assert!(joined.has_chunk_index());
+        assert_eq!(joined.min_fill_factor(), 0.5);
+        assert_eq!(joined.chunk_pool_limit(), 1);
+        assert_eq!(joined.get(30), Some(&30));
+    }
+
+    #[test]
+    #[should_panic(expected = "shard count should be > 0")]
+    fn split_into_zero_shards() {
+        ChunkList::<i32, 4>::from_iter(0..5).split_into_shards(0);
+    }
+
+    #[test]
+    fn chunk_ring() {
+        use std::thread;
+        use super::concurrent::chunk_ring;
+        let (mut producer, mut consumer) = chunk_ring::<i32, 2>(2);
+        for value in 0..6 {
+            assert_eq!(producer.try_push(value), Ok(()));
+        }
+        assert_eq!(producer.try_push(6), Err(6));
+        assert_eq!(consumer.pop_chunk().unwrap().as_slice(), [0, 1]);
+        assert_eq!(producer.try_push(6), Ok(()));
+        assert_eq!(consumer.pop_chunk().unwrap().as_slice(), [2, 3]);
+        producer.flush();
+        assert_eq!(consumer.pop_chunk().unwrap().as_slice(), [4, 5]);
+        assert_eq!(consumer.pop_chunk().unwrap().as_slice(), [6]);
+        assert!(consumer.pop_chunk().is_none());
+        assert!(!consumer.is_disconnected());
+
+        let (mut producer, mut consumer) = chunk_ring::<u32, 16>(4);
+        let sender = thread::spawn(move || (0..10_000).for_each(|value| producer.push(value)));
+        let mut received = Vec::new();
+        loop {
+            let disconnected = consumer.is_disconnected();
+            match consumer.pop_chunk() {
+                Some(chunk) => received.extend_from_slice(chunk.as_slice()),
+                None if disconnected => break,
+                None => thread::yield_now(),
+            }
+        }
+        sender.join().unwrap();
+        assert_eq!(received, (0..10_000).collect::<Vec<_>>());
+
+        let (mut producer, consumer) = chunk_ring::<u32, 4>(1);
+        drop(consumer);
+        (0..100).for_each(|value| producer.push(value));
+    }
+
+    #[test]
+    fn append_log() {
+        use std::thread;
+        use super::concurrent::AppendLog;
+        let log = AppendLog::<usize, 4>::new();
+        assert_eq!(log.iter().next(), None);
+        for value in 0..50 {
+            assert_eq!(log.push_back(value), value);
+        }
+        assert_eq!(log.claimed_len(), 50);
+        assert!(log.iter().copied().eq(0..50));
+        assert_eq!(log.get(49), Some(&49));
+        assert_eq!(log.get(50), None);
+
+        let log = AppendLog::<(usize, usize), 8>::new();
+        thread::scope(|scope| {
+            for writer in 0..4 {
+                let log = &log;
+                scope.spawn(move || (0..1000).for_each(|i| { log.push_back((writer, i)); }));
+            }
+            scope.spawn(|| {
+                let prefix: Vec<_> = log.iter().collect();
+                assert!(prefix.len() <= log.claimed_len());
+            });
+        });
+        let mut values: Vec<_> = log.iter().copied().collect();
+        for writer in 0..4 {
+            assert!(values.iter().filter(|value| value.0 == writer).map(|value| value.1).eq(0..1000));
+        }
+        values.sort();
+        assert_eq!(values.len(), 4000);
+        values.dedup();
+        assert_eq!(values.len(), 4000);
+    }
+
+    #[test]
+    fn persistent_chunk_list() {
+        use super::PersistentChunkList;
+        let mut list = PersistentChunkList::<i32, 4>::from_iter(0..20);
+        let snapshot = list.clone();
+        assert_eq!(list.shared_chunks_count(&snapshot), 5);
+        assert_eq!(list.set(5, 50), 5);
+        list.push_back(20);
+        assert_eq!(list.pop_front(), Some(0));
+        assert_eq!(list.shared_chunks_count(&snapshot), 3);
+        assert!(snapshot.iter().copied().eq(0..20));
+        assert_eq!(list[4], 50);
+        assert_eq!(list.elements_count(), 20);
+        assert_eq!(list.iter().len(), 20);
+        assert_eq!(list.iter().next_back(), Some(&20));
+
+        let updated = snapshot.update(19, -1);
+        assert_eq!((snapshot[19], updated[19]), (19, -1));
+        assert_eq!(updated.shared_chunks_count(&snapshot), 4);
+        list.push_front(-1);
+        assert_eq!((list.front(), list.back()), (Some(&-1), Some(&20)));
+        assert_eq!(list.get(21), None);
+        let chunk_list = ChunkList::from(list.clone());
+        assert!(chunk_list.iter().eq(list.iter()));
+        assert_eq!(PersistentChunkList::from(chunk_list), list);
+        while list.pop_back().is_some() {}
+        assert_eq!((list.elements_count(), list.chunks_count()), (0, 0));
+    }
+
+    #[test]
+    fn snapshot_rollback() {
+        use super::PersistentChunkList;
+        let mut list = PersistentChunkList::<i32, 4>::from_iter(0..40);
+        let snapshot = list.snapshot();
+        list.set(0, -1);
+        list.set(1, -2);
+        list.push_back(40);
+        assert_eq!(snapshot.changed_chunks_count(&list), 2);
+        list.rollback(snapshot);
+        assert!(list.iter().copied().eq(0..40));
+
+        let snapshot = list.snapshot();
+        while list.pop_front().is_some() {}
+        assert_eq!(snapshot.changed_chunks_count(&list), 0);
+        list.rollback(snapshot);
+        assert_eq!(list.elements_count(), 40);
+    }
+
+    #[test]
+    fn io_write() {
+        use std::io::{IoSlice, Write};
+        let mut list = ChunkList::<u8, 4>::new();
+        list.write_all(b"hello").unwrap();
+        let name = "world";
+        write!(list, ", {}!", name).unwrap();
+        let written = list.write_vectored(&[IoSlice::new(b" a"), IoSlice::new(b""), IoSlice::new(b" b")]).unwrap();
+        assert_eq!(written, 4);
+        list.flush().unwrap();
+        assert_eq!(list.iter().copied().collect::<Vec<_>>(), b"hello, world! a b");
+        assert_eq!(list.chunks_count(), 5);
+    }
+
+    #[test]
+    fn io_read() {
+        use std::io::{BufRead, Read};
+        let mut list = ChunkList::<u8, 4>::from_iter(*b"first line\nsecond\nrest");
+        assert_eq!(list.fill_buf().unwrap(), b"firs");
+        list.consume(2);
+        list.add_new_chunk_front();
+        assert_eq!(list.fill_buf().unwrap(), b"rs");
+        let mut line = String::new();
+        list.read_line(&mut line).unwrap();
+        assert_eq!(line, "rst line\n");
+        let mut buf = [0; 3];
+        assert_eq!(list.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"sec");
+        let mut rest = Vec::new();
+        list.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"ond\nrest");
+        assert_eq!(list.elements_count(), 0);
+        assert_eq!(list.read(&mut buf).unwrap(), 0);
+        assert_eq!(list.fill_buf().unwrap(), b"");
+    }
+
+    #[test]
+    fn extend_from_reader() {
+        use std::io::{self, Read};
+        let data: Vec<u8> = (0..100).collect();
+        let mut list = ChunkList::<u8, 16>::from_iter([200, 201]);
+        assert_eq!(list.extend_from_reader(&mut &data[..]).unwrap(), 100);
+        assert_eq!(list.elements_count(), 102);
+        assert!(list.iter().copied().skip(2).eq(0..100));
+        assert_eq!(list.chunks_count(), 7);
+
+        let mut list = ChunkList::<u8, 16>::new();
+        assert_eq!(list.extend_from_reader_limit(&mut &data[..], 20).unwrap(), 20);
+        assert_eq!(list.extend_from_reader_limit(&mut &[][..], 20).unwrap(), 0);
+        assert_eq!(list.chunks_count(), 2);
+        assert!(list.iter().copied().eq(0..20));
+
+        struct Failing(u8);
+        impl Read for Failing {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.0 += 1;
+                match self.0 {
+                    1 => Err(io::ErrorKind::Interrupted.into()),
+                    2 => {
+                        buf[..3].copy_from_slice(b"abc");
+                        Ok(3)
+                    }
+                    _ => Err(io::ErrorKind::BrokenPipe.into()),
+                }
+            }
+        }
+        let mut list = ChunkList::<u8, 16>::new();
+        let error = list.extend_from_reader(&mut Failing(0)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::BrokenPipe);
+        assert_eq!(list, *b"abc");
+        assert_eq!(list.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn find() {
+        let text = b"abcabcabd, abcabd";
+        let list = ChunkList::<u8, 4>::from_iter(text.iter().copied());
+        let naive_find = |needle: &[u8]| text.windows(needle.len()).position(|window| window == needle);
+        let naive_rfind = |needle: &[u8]| text.windows(needle.len()).rposition(|window| window == needle);
+        for needle in [&b"abd"[..], b"cab", b"abcabd", b"d, a", b"bcabcabd, abcab", b"x", b"abdx", text] {
+            assert_eq!(list.find(needle), naive_find(needle));
+            assert_eq!(list.rfind(needle), naive_rfind(needle));
+        }
+        assert_eq!(list.find(b""), Some(0));
+        assert_eq!(list.rfind(b""), Some(text.len()));
+        assert_eq!(ChunkList::<u8, 4>::new().find(b"a"), None);
+        let mut list = ChunkList::<u8, 4>::from_iter(*b"aab");
+        list.add_new_chunk_back();
+        list.extend(*b"ba");
+        assert_eq!((list.find(b"bb"), list.rfind(b"ab")), (Some(2), Some(1)));
+    }
+
+    #[test]
+    fn chunk_string() {
+        use super::ChunkString;
+        let mut string = ChunkString::<4>::from("héllo");
+        string.push_str(", wörld");
+        string.push('!');
+        assert_eq!(string, "héllo, wörld!");
+        assert_eq!((string.len(), string.chars_count()), (15, 13));
+        string.insert_str(8, "ünïcode ");
+        string.insert(0, '→');
+        assert_eq!(string.to_string(), "→héllo, ünïcode wörld!");
+        assert_eq!(string.char_at(2), Some('é'));
+        assert_eq!(string.char_at(22), None);
+        assert_eq!(string.char_to_byte(2), Some(4));
+        assert_eq!(string.char_to_byte(22), Some(string.len()));
+        string.remove_range(11..21);
+        assert_eq!(string, "→héllo, wörld!");
+        assert!(!string.is_char_boundary(1));
+        assert_eq!(format!("{:?}", string), "\"→héllo, wörld!\"");
+        assert!(string.chars().eq("→héllo, wörld!".chars()));
+        std::fmt::Write::write_fmt(&mut string, format_args!(" {}", 42)).unwrap();
+        assert_eq!(string, "→héllo, wörld! 42");
+    }
+
+    #[test]
+    #[should_panic(expected = "byte index 1 is not a char boundary")]
+    fn chunk_string_insert_inside_char() {
+        super::ChunkString::<4>::from("é").insert_str(1, "x");
+    }
+
+    #[test]
+    fn chunk_str() {
+        use super::ChunkListError;
+        let text = "aé→𝄞b";
+        for split in 0..=text.len() {
+            let mut bytes = ChunkList::<u8, 3>::from_iter(text.as_bytes()[..split].iter().copied());
+            bytes.add_new_chunk_back();
+            bytes.extend(text.as_bytes()[split..].iter().copied());
+            let chunk_str = bytes.as_chunk_str().unwrap();
+            assert_eq!(chunk_str.to_string(), text);
+            assert!(chunk_str.chars().eq(text.chars()));
+            assert!(chunk_str.char_indices().eq(text.char_indices()));
+        }
+        let invalid = |bytes: &[u8]| ChunkList::<u8, 2>::from_iter(bytes.iter().copied()).as_chunk_str().err();
+        assert_eq!(invalid(b"ab\xff"), Some(ChunkListError::InvalidUtf8 { valid_up_to: 2 }));
+        assert_eq!(invalid(b"a\xe2\x86"), Some(ChunkListError::InvalidUtf8 { valid_up_to: 1 }));
+        assert_eq!(invalid(b"a\xe2\x86a"), Some(ChunkListError::InvalidUtf8 { valid_up_to: 1 }));
+        assert_eq!(invalid("é→".as_bytes()), None);
+        let string = super::ChunkString::<2>::from("→x");
+        assert_eq!(format!("{:?}", string.as_chunk_str()), "\"→x\"");
+    }
+
+    #[test]
+    fn gap_buffer() {
+        let mut list = ChunkList::<char, 4>::from_iter("hello world".chars());
+        list.set_chunk_index(true);
+        let mut cursor = list.cursor_front_mut();
+        cursor.seek(5);
+        for c in ", brave new".chars() {
+            cursor.gap_insert_before(c);
+        }
+        assert_eq!(cursor.current(), Some(&mut ' '));
+        assert_eq!(cursor.index(), Some(16));
+        assert_eq!(cursor.gap_remove_before(), Some('w'));
+        assert_eq!(cursor.gap_remove_before(), Some('e'));
+        cursor.gap_insert_before('W');
+        assert_eq!(cursor.index(), Some(15));
+        assert_eq!(list.iter().collect::<String>(), "hello, brave nW world");
+        assert_eq!(list.check_invariants(), Ok(()));
+        assert_eq!(list[14], 'W');
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_prev();
+        cursor.gap_insert_before('!');
+        assert_eq!(cursor.index(), None);
+        cursor.seek(2);
+        assert_eq!(cursor.gap_remove_before(), Some('e'));
+        assert_eq!(cursor.gap_remove_before(), Some('h'));
+        assert_eq!(cursor.gap_remove_before(), None);
+        assert_eq!(cursor.current(), Some(&mut 'l'));
+        for _ in 0..4 {
+            cursor.move_next();
+        }
+        for _ in 0..5 {
+            cursor.gap_remove_before();
+        }
+        assert_eq!(list.iter().collect::<String>(), " brave nW world!");
+        assert_eq!(list.check_invariants(), Ok(()));
+    }
+
+    #[test]
+    fn spilling_chunk_list() {
+        use super::{FileStore, SpillingChunkList};
+        let mut list = SpillingChunkList::<u32, 4, _>::new(FileStore::temp().unwrap(), 2);
+        for value in 0..40 {
+            list.push_back(value).unwrap();
+        }
+        assert_eq!((list.chunks_count(), list.resident_chunks_count()), (10, 2));
+        for i in (0..40).rev() {
+            assert_eq!(list.get(i as usize).unwrap(), Some(&i));
+        }
+        assert_eq!(list.resident_chunks_count(), 2);
+        *list.get_mut(5).unwrap().unwrap() = 500;
+        list.get(39).unwrap();
+        list.get(20).unwrap();
+        assert_eq!(list.get(5).unwrap(), Some(&500));
+        list.push_front(1000).unwrap();
+        assert_eq!(list.pop_front().unwrap(), Some(1000));
+        assert_eq!(list.pop_back().unwrap(), Some(39));
+        assert_eq!(list.get(40).unwrap(), None);
+        list.set_memory_budget(1).unwrap();
+        assert_eq!(list.resident_chunks_count(), 1);
+        let chunk_list = list.into_chunk_list().unwrap();
+        let expected: Vec<u32> = (0..39).map(|value| if value == 5 { 500 } else { value }).collect();
+        assert_eq!(chunk_list, expected);
+    }
+
+    #[test]
+    fn write_to_read_from() {
+        use super::ChunkList;
+        let mut list = ChunkList::<i32, 4>::new();
+        list.extend(0..10);
+        list.push_front(-1);
+        let mut bytes = Vec::new();
+        list.write_to(&mut bytes).unwrap();
+        let checksums_len = if cfg!(feature = "checksums") { 4 } else { 0 };
+        assert_eq!(bytes.len(), 8 + 1 + 4 * (12 + checksums_len) + 11 * 4);
+        let read = ChunkList::<i32, 4>::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read, list);
+        assert_eq!(read.chunks_count(), list.chunks_count());
+
+        let empty = ChunkList::<i32, 4>::new();
+        let mut bytes = Vec::new();
+        empty.write_to(&mut bytes).unwrap();
+        assert_eq!(ChunkList::<i32, 4>::read_from(&mut bytes.as_slice()).unwrap(), empty);
+
+        let mut bytes = Vec::new();
+        list.write_to(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        assert!(ChunkList::<i32, 4>::read_from(&mut bytes.as_slice()).is_err());
+        assert!(ChunkList::<i32, 2>::read_from(&mut Vec::new().as_slice()).is_err());
+    }
+
+    #[cfg(feature = "checksums")]
+    #[test]
+    fn checksums() {
+        use super::{crc32, ChunkList, SpillStore, SpillingChunkList};
+        use std::{collections::HashMap, io};
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let mut list = ChunkList::<u16, 4>::new();
+        list.extend(0..10);
+        let checksums = list.chunk_checksums();
+        assert_eq!(list.verify(&checksums), Ok(()));
+        list[5] = 50;
+        assert_eq!(list.verify(&checksums), Err(vec![1]));
+        list.push_back(10);
+        assert_eq!(list.verify(&checksums), Err(vec![1, 2]));
+
+        let mut bytes = Vec::new();
+        list.write_to(&mut bytes).unwrap();
+        assert_eq!(ChunkList::<u16, 4>::read_from(&mut bytes.as_slice()).unwrap(), list);
+        bytes[8 + 1 + 12] ^= 1;
+        let error = ChunkList::<u16, 4>::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        /// Flips a bit of every stored chunk.
+        #[derive(Default)]
+        struct CorruptingStore(HashMap<u64, Vec<u8>>);
+        impl SpillStore for CorruptingStore {
+            fn store(&mut self, key: u64, bytes: &[u8]) -> io::Result<()> {
+                let mut bytes = bytes.to_vec();
+                bytes[0] ^= 1;
+                self.0.insert(key, bytes);
+                Ok(())
+            }
+
+            fn take(&mut self, key: u64) -> io::Result<Vec<u8>> {
+                Ok(self.0.remove(&key).unwrap())
+            }
+        }
+
+        let mut list = SpillingChunkList::<u16, 4, _>::new(CorruptingStore::default(), 1);
+        for value in 0..8 {
+            list.push_back(value).unwrap();
+        }
+        assert_eq!(list.get(0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compressed_store() {
+        use super::{CompressedStore, SpillingChunkList};
+        let mut list = SpillingChunkList::<u64, 256, _>::new(CompressedStore::new(), 1);
+        for value in 0..1024 {
+            list.push_back(value % 4).unwrap();
+        }
+        assert_eq!(list.resident_chunks_count(), 1);
+        assert!(list.store().compressed_len() < 3 * 256 * 8 / 4);
+        assert_eq!(list.get(7).unwrap(), Some(&3));
+        assert_eq!(list.resident_chunks_count(), 1);
+        let list = list.into_chunk_list().unwrap();
+        assert!(list.iter().copied().eq((0..1024).map(|value| value % 4)));
+    }
 
-}
\ No newline at end of file
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize() {
+        use zeroize::{Zeroize, Zeroizing};
+        let mut list = ChunkList::<u64, 4>::new();
+        list.extend(0..10);
+        list.pop_front();
+        list.zeroize();
+        assert_eq!(list.elements_count(), 0);
+        assert_eq!(list.chunks_count(), 0);
+        assert_eq!(list.iter().next(), None);
+        list.push_back(1);
+        assert_eq!(list, [1]);
+
+        let mut list = Zeroizing::new(ChunkList::<u8, 4>::new());
+        list.extend(b"secret");
+        assert_eq!(list.elements_count(), 6);
+    }
+
+    #[cfg(feature = "zeroize")]
+    #[test]
+    fn zeroize_released_buffers() {
+        use std::rc::Rc;
+        use super::GrowingChunkPolicy;
+        static POLICY: GrowingChunkPolicy = GrowingChunkPolicy { initial_capacity: 1 };
+        let counter = Rc::new(());
+        let mut list = ChunkList::<Rc<()>, 4>::new();
+        list.set_chunk_policy(&POLICY);
+        list.extend((0..40).map(|_| Rc::clone(&counter)));
+        for _ in 0..30 {
+            list.pop_front();
+        }
+        assert_eq!(Rc::strong_count(&counter), 11);
+        list.push_front(Rc::clone(&counter));
+        list.push_front(Rc::clone(&counter));
+        list.sort_by(|_, _| std::cmp::Ordering::Equal);
+        list.shrink_to_fit();
+        assert_eq!(list.elements_count(), 12);
+        assert_eq!(Rc::strong_count(&counter), 13);
+        drop(list);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn pinned_chunk_list() {
+        use super::PinnedChunkList;
+        let mut list = PinnedChunkList::<u32, 4>::new();
+        let mut addresses = Vec::new();
+        for value in 0..6 {
+            addresses.push((value, list.push_back(value) as *const u32 as usize));
+        }
+        for value in 100..106 {
+            addresses.push((value, list.push_front(value) as *const u32 as usize));
+        }
+        let check = |list: &PinnedChunkList<u32, 4>, addresses: &[(u32, usize)]| {
+            for &(value, address) in addresses {
+                let element = list.iter().find(|&&element| element == value).unwrap();
+                assert_eq!(element as *const u32 as usize, address);
+            }
+        };
+        check(&list, &addresses);
+        assert_eq!(list.pop_back(), Some(5));
+        assert_eq!(list.pop_front(), Some(105));
+        addresses.retain(|&(value, _)| value != 5 && value != 105);
+        for value in 200..210 {
+            addresses.push((value, list.push_back(value) as *const u32 as usize));
+            addresses.push((value + 100, list.push_front(value + 100) as *const u32 as usize));
+        }
+        check(&list, &addresses);
+        assert_eq!(list.elements_count(), 30);
+        assert_eq!(list.front(), Some(&309));
+        assert_eq!(list.back(), Some(&209));
+        assert_eq!(list.get(14), Some(&100));
+        *list.get_mut(14).unwrap() = 1000;
+        assert_eq!(list.get(14), Some(&1000));
+
+        // With a single chunk, pops from either end close the other end for pushes.
+        let mut list = PinnedChunkList::<u32, 4>::new();
+        list.push_back(0);
+        list.push_back(1);
+        list.pop_front();
+        let address = list.front().unwrap() as *const u32 as usize;
+        list.push_back(2);
+        list.push_back(3);
+        list.push_back(4);
+        assert_eq!(list.front().unwrap() as *const u32 as usize, address);
+        assert!(list.iter().copied().eq(1..5));
+        list.clear();
+        assert_eq!(list.pop_front(), None);
+    }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn testing_strategies() {
+        use arbitrary::{Arbitrary, Unstructured};
+        use proptest::{strategy::{Strategy, ValueTree}, test_runner::TestRunner};
+        use super::testing;
+        let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+        let list = ChunkList::<u16, 4>::arbitrary(&mut Unstructured::new(&bytes)).unwrap();
+        list.check_invariants().unwrap();
+        assert!(list.elements_count() > 0);
+
+        let mut runner = TestRunner::deterministic();
+        let strategy = testing::chunk_list::<u16, 4>(proptest::num::u16::ANY, 0..64);
+        let mut partial_chunks = false;
+        for _ in 0..32 {
+            let list = strategy.new_tree(&mut runner).unwrap().current();
+            list.check_invariants().unwrap();
+            assert!(list.elements_count() <= 64);
+            partial_chunks |= list.chunks_count() > list.elements_count().div_ceil(4);
+        }
+        assert!(partial_chunks);
+    }
+
+    /// Applies random operations to a `ChunkList` and a `VecDeque` and compares them after every step.
+    fn run_model<const N: usize>(seed: u64, steps: usize, chunk_index: bool) {
+        use std::collections::VecDeque;
+        let mut state = seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1;
+        let mut random = move |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % bound.max(1)
+        };
+        let mut list = ChunkList::<u16, N>::new();
+        list.set_chunk_index(chunk_index);
+        let mut model = VecDeque::new();
+        for step in 0..steps {
+            let value = step as u16;
+            let len = model.len();
+            // Positions may be one past the end to exercise out of bounds accesses.
+            let i = random(len + 2);
+            let operation = random(20);
+            match operation {
+                0..=2 => {
+                    list.push_back(value);
+                    model.push_back(value);
+                }
+                3..=4 => {
+                    list.push_front(value);
+                    model.push_front(value);
+                }
+                5 => assert_eq!(list.pop_back(), model.pop_back()),
+                6 => assert_eq!(list.pop_front(), model.pop_front()),
+                7..=8 => {
+                    let i = i.min(len);
+                    list.insert(i, value);
+                    model.insert(i, value);
+                }
+                9..=10 => assert_eq!(list.remove(i), model.remove(i)),
+                11 => {
+                    assert_eq!(list.get(i), model.get(i));
+                    if let (Some(element), Some(expected)) = (list.get_mut(i), model.get_mut(i)) {
+                        *element = value;
+                        *expected = value;
+                    }
+                }
+                12 => {
+                    let i = i.min(len);
+                    let mut tail = list.split_off(i);
+                    let mut expected = model.split_off(i);
+                    assert!(tail.iter().eq(&expected));
+                    tail.push_front(value);
+                    expected.push_front(value);
+                    list.append(&mut tail);
+                    model.append(&mut expected);
+                }
+                13 => {
+                    let (start, end) = (random(len + 1), random(len + 1));
+                    let range = start.min(end)..start.max(end);
+                    assert!(list.drain(range.clone()).eq(model.drain(range)));
+                }
+                14 => {
+                    list.truncate(i);
+                    model.truncate(i);
+                }
+                15 => {
+                    list.truncate_front(i);
+                    model.drain(..len.saturating_sub(i));
+                }
+                16 => {
+                    let modulus = random(5) as u16 + 2;
+                    list.retain(|value| value % modulus != 0);
+                    model.retain(|value| value % modulus != 0);
+                }
+                17 => {
+                    let j = random(len);
+                    if i < len {
+                        list.swap(i, j);
+                        model.swap(i, j);
+                    }
+                    assert_eq!(list.swap_remove_back(i), model.swap_remove_back(i));
+                }
+                18 => {
+                    let k = i.min(len);
+                    list.rotate_left(k);
+                    model.rotate_left(k);
+                }
+                _ => {
+                    let values: Vec<u16> = (0..random(2 * N + 1) as u16).map(|offset| value + offset).collect();
+                    list.extend_from_slice(&values);
+                    model.extend(values);
+                }
+            }
+            assert_eq!(list.elements_count(), model.len(), "seed {} step {} operation {}", seed, step, operation);
+            assert!(list.iter().eq(&model), "seed {} step {} operation {}", seed, step, operation);
+            assert!(list.iter().rev().eq(model.iter().rev()));
+            assert_eq!((list.front(), list.back()), (model.front(), model.back()));
+            list.check_invariants().unwrap();
+        }
+    }
+
+    #[test]
+    fn model_against_vec_deque() {
+        for seed in 0..64 {
+            let chunk_index = seed % 2 == 1;
+            run_model::<1>(seed, 200, chunk_index);
+            run_model::<3>(seed, 500, chunk_index);
+            run_model::<8>(seed, 500, chunk_index);
+        }
+    }
+
+    #[test]
+    fn push_chunk() {
+        let mut list = ChunkList::<u32, 4>::new();
+        list.set_chunk_index(true);
+        list.push_back(0);
+        let chunk = vec![1, 2, 3, 4];
+        let address = chunk.as_ptr();
+        list.push_chunk_back(chunk);
+        assert_eq!(&list[1] as *const u32, address);
+        list.push_chunk_back((5..15).collect::<Vec<_>>());
+        list.push_chunk_back(Vec::new());
+        assert_eq!(list.chunks().map(|chunk| chunk.len()).collect::<Vec<_>>(), [1, 4, 4, 4, 2]);
+        list.push_chunk_front([0; 0]);
+        list.push_chunk_front([100, 101, 102, 103]);
+        assert_eq!(list.chunks_count(), 6);
+        assert_eq!(list.elements_count(), 19);
+        assert_eq!(list[0], 100);
+        assert_eq!(list[18], 14);
+        list.check_invariants().unwrap();
+        assert!(list.iter().copied().eq((100..104).chain(0..15)));
+    }
+
+    #[test]
+    fn pop_chunk() {
+        let mut list = ChunkList::<u32, 4>::new();
+        list.set_chunk_index(true);
+        list.extend(0..10);
+        let address = &list[8] as *const u32;
+        let back = list.pop_chunk_back().unwrap();
+        assert_eq!(back, [8, 9]);
+        assert_eq!(back.as_ptr(), address);
+        assert_eq!(list.pop_chunk_front(), Some(vec![0, 1, 2, 3]));
+        assert_eq!(list.elements_count(), 4);
+        assert_eq!(list[3], 7);
+        list.check_invariants().unwrap();
+        assert_eq!(list.pop_chunk_back(), Some(vec![4, 5, 6, 7]));
+        assert_eq!(list.pop_chunk_back(), None);
+        assert_eq!(list.pop_chunk_front(), None);
+    }
+
+    #[test]
+    fn raw_parts() {
+        use super::ChunkListError;
+        let mut list = ChunkList::<u32, 4>::new();
+        list.extend(0..10);
+        list.push_front(100);
+        let (chunks, elements_count) = list.clone().into_raw_parts();
+        assert_eq!(chunks, [vec![100], vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+        assert_eq!(elements_count, 11);
+        let rebuilt = ChunkList::<u32, 4>::from_raw_parts(chunks, elements_count).unwrap();
+        assert_eq!(rebuilt, list);
+        assert_eq!(rebuilt.chunks_count(), 4);
+
+        let rebuilt = ChunkList::<u32, 4>::from_raw_parts(vec![vec![], vec![1], vec![]], 1).unwrap();
+        assert_eq!(rebuilt.chunks_count(), 1);
+        rebuilt.check_invariants().unwrap();
+        assert_eq!(
+            ChunkList::<u32, 4>::from_raw_parts(vec![vec![1], vec![0; 5]], 6),
+            Err(ChunkListError::ChunkOverflow { chunk_i: 1, len: 5 }),
+        );
+        assert_eq!(
+            ChunkList::<u32, 4>::from_raw_parts(vec![vec![1, 2]], 3),
+            Err(ChunkListError::ElementsCountMismatch { expected: 3, counted: 2 }),
+        );
+    }
+
+    #[test]
+    fn from_fn() {
+        let list = ChunkList::<usize, 4>::from_fn(10, |i| i * i);
+        assert!(list.iter().copied().eq((0..10).map(|i| i * i)));
+        assert_eq!(list.chunks_count(), 3);
+        list.check_invariants().unwrap();
+        assert_eq!(ChunkList::<usize, 4>::from_fn(0, |i| i).chunks_count(), 0);
+        assert_eq!(ChunkList::<usize, 4>::from_fn(8, |i| i).chunks_count(), 2);
+    }
+
+    #[test]
+    fn pop_n() {
+        let mut list = ChunkList::<u32, 4>::new();
+        list.set_chunk_index(true);
+        list.extend(0..20);
+        let front = list.pop_front_n(6);
+        assert!(front.iter().copied().eq(0..6));
+        let back = list.pop_back_n(8);
+        assert!(back.iter().copied().eq(12..20));
+        assert_eq!(back.chunks_count(), 2);
+        assert!(list.iter().copied().eq(6..12));
+        assert_eq!(list[0], 6);
+        assert!(list.has_chunk_index());
+        list.check_invariants().unwrap();
+        assert_eq!(list.pop_front_n(0).elements_count(), 0);
+        assert_eq!(list.pop_back_n(100).elements_count(), 6);
+        assert_eq!(list.pop_front_n(1).elements_count(), 0);
+        assert_eq!(list.pop_back_n(1).elements_count(), 0);
+    }
+}